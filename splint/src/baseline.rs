@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ty::LintError;
+
+/// A pre-existing lint, identified without its line number so it survives unrelated edits
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BaselineEntry {
+    pub rule: String,
+    pub file: String,
+    pub hash: String,
+}
+
+/// Lints recorded by `--write-baseline`, suppressed on later runs
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Baseline {
+    pub findings: Vec<BaselineEntry>,
+}
+
+/// FNV-1a, as std's hasher isn't stable across Rust versions
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl LintError {
+    /// A stable identifier for the lint from its rule and matched tokens,
+    /// so it survives edits elsewhere in the file that move it
+    pub fn fingerprint(&self) -> String {
+        let mut key = self.rule.name.clone();
+        for t in &self.window {
+            key.push('\0');
+            key.push_str(t.value());
+        }
+
+        format!("{:016x}", fnv1a(&key))
+    }
+
+    /// Identifies the lint by its file and fingerprint
+    pub fn baseline_entry(&self) -> BaselineEntry {
+        BaselineEntry {
+            rule: self.rule.name.clone(),
+            file: self.source.name().to_string(),
+            hash: self.fingerprint(),
+        }
+    }
+}
+
+impl Baseline {
+    pub fn new(lints: &[LintError]) -> Self {
+        Self {
+            findings: lints.iter().map(LintError::baseline_entry).collect(),
+        }
+    }
+
+    /// Drops lints recorded in the baseline, each entry suppressing one matching lint
+    pub fn suppress(&self, lints: Vec<LintError>) -> Vec<LintError> {
+        let mut counts = HashMap::<&BaselineEntry, usize>::new();
+        for f in &self.findings {
+            *counts.entry(f).or_default() += 1;
+        }
+
+        lints
+            .into_iter()
+            .filter(|e| match counts.get_mut(&e.baseline_entry()) {
+                Some(n) if *n > 0 => {
+                    *n -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_str, ty::Rules};
+
+    #[test]
+    fn suppresses_recorded_lints_by_content() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        )
+        .unwrap();
+        let old = lint_str("fn f() {\n    a.unwrap();\n}", "a.rs", &rules).unwrap();
+        let baseline = Baseline::new(&old);
+
+        // Moving the lint to another line keeps it suppressed, a new one isn't
+        let source = "fn f() {\n\n    a.unwrap();\n    b.unwrap();\n}";
+        let new = baseline.suppress(lint_str(source, "a.rs", &rules).unwrap());
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].position.line_text, "    b.unwrap();");
+    }
+
+    #[test]
+    fn fingerprints_follow_the_matched_tokens() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            range = [0, 2]
+            pattern = [{ kind = "Ident" }, ["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        )
+        .unwrap();
+        let fingerprints = |source: &str| {
+            lint_str(source, "a.rs", &rules)
+                .unwrap()
+                .iter()
+                .map(LintError::fingerprint)
+                .collect::<Vec<_>>()
+        };
+
+        let old = fingerprints("fn f() {\n    a.unwrap();\n}");
+        assert_eq!(old, fingerprints("fn f() {\n    a.unwrap();\n}"));
+        assert_eq!(old, fingerprints("fn g() {\n\n    a . unwrap();\n}"));
+        assert_ne!(old, fingerprints("fn f() {\n    b.unwrap();\n}"));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{compiler::CompilerSpan, ty::LintError};
+
+/// Escapes text for use in a double-quoted XML attribute
+pub(crate) fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
+/// Formats lints as a Checkstyle XML report, with a `file` element per linted file in order
+pub fn checkstyle(lints: &[LintError]) -> String {
+    let mut files: Vec<(&str, Vec<&LintError>)> = Vec::new();
+    for e in lints {
+        let name = e.source.name();
+        match files.iter_mut().find(|(f, _)| *f == name) {
+            Some((_, v)) => v.push(e),
+            None => files.push((name, vec![e])),
+        }
+    }
+
+    let mut out =
+        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n");
+    for (name, lints) in files {
+        out.push_str(&format!("  <file name=\"{}\">\n", escape_attr(name)));
+        for e in lints {
+            let span: CompilerSpan = e.into();
+            let severity = match e.fails {
+                true => "error",
+                false => "warning",
+            };
+
+            out.push_str(&format!(
+                "    <error line=\"{}\" column=\"{}\" severity=\"{severity}\" message=\"{}\" source=\"{}\"/>\n",
+                span.line_start,
+                span.column_start,
+                escape_attr(&e.to_string()),
+                escape_attr(&e.rule.name),
+            ));
+        }
+        out.push_str("  </file>\n");
+    }
+
+    out.push_str("</checkstyle>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_str, ty::Rules};
+
+    #[test]
+    fn groups_errors_by_file() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "No <unwrap> & \"friends\""
+            fails = true
+            range = [0, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        )
+        .unwrap();
+        let mut lints = lint_str("fn f() {\n    a.unwrap();\n}", "a.rs", &rules).unwrap();
+        lints.extend(lint_str("fn f() { b.unwrap(); c.unwrap(); }", "b.rs", &rules).unwrap());
+        let xml = checkstyle(&lints);
+
+        assert_eq!(xml.matches("<file ").count(), 2);
+        assert_eq!(xml.matches("<error ").count(), 3);
+        assert!(xml.contains(
+            "  <file name=\"a.rs\">\n    <error line=\"2\" column=\"6\" severity=\"error\" \
+             message=\"u: No &lt;unwrap&gt; &amp; &quot;friends&quot;\" source=\"u\"/>\n  </file>"
+        ));
+        assert!(xml.ends_with("</checkstyle>"));
+    }
+}
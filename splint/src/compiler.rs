@@ -2,20 +2,51 @@ use std::path::Path;
 use std::str::FromStr;
 
 use miette::Report;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+    JsonSchema,
+};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use to_and_fro::{default, ToAndFro};
+use to_and_fro::ToAndFro;
 
-use crate::ty::LintError;
+use crate::ty::{LintError, Position, Suggestion};
 
-#[derive(ToAndFro, Clone)]
+/// How confident a suggestion is, `--fix` only applies machine applicable ones
+#[derive(ToAndFro, Clone, Debug, Default)]
 #[serde]
-enum SuggestionApplicability {
+pub enum SuggestionApplicability {
     MachineApplicable,
     HasPlaceholders,
     MaybeIncorrect,
+    #[default]
     Unspecified,
 }
 
+impl JsonSchema for SuggestionApplicability {
+    fn schema_name() -> String {
+        "SuggestionApplicability".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(
+                [
+                    "MachineApplicable",
+                    "HasPlaceholders",
+                    "MaybeIncorrect",
+                    "Unspecified",
+                ]
+                .map(Into::into)
+                .to_vec(),
+            ),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CompilerSpan {
     /// Byte offset of the start of the span, relative to the start of the file
@@ -24,20 +55,20 @@ pub struct CompilerSpan {
     byte_end: usize,
 
     /// Column offset of the start of the span, in the source file
-    column_start: usize,
+    pub(crate) column_start: usize,
     /// Column offset of the end of the span, in the source file
-    column_end: usize,
+    pub(crate) column_end: usize,
 
     /// Line number of the start of the span, in the source file
-    line_end: usize,
+    pub(crate) line_end: usize,
     /// Line number of the end of the span, in the source file
-    line_start: usize,
+    pub(crate) line_start: usize,
 
     /// I'm not sure what this does right now
     expansion: Option<()>,
 
     /// Name of the span's source file
-    file_name: String,
+    pub(crate) file_name: String,
     /// Whether the span is primary
     is_primary: bool,
 
@@ -165,49 +196,64 @@ pub struct CompilerMessageTarget {
     test: bool,
 }
 
-impl Into<CompilerSpan> for &LintError {
-    fn into(self) -> CompilerSpan {
-        let v = self.window.clone();
-
-        let byte_start = v.first().unwrap().span().byte_range().start;
-        let byte_end = v.last().unwrap().span().byte_range().end;
-
-        let column_start = v.first().unwrap().span().start().column + 1;
-        let column_end = v.last().unwrap().span().end().column + 1;
-
-        let line_start = v.first().unwrap().span().start().line;
-        let line_end = v.last().unwrap().span().end().line;
-
-        let highlight_end = byte_start - self.line.1;
-        let highlight_start = highlight_end - (byte_end - byte_start);
+impl From<&LintError> for CompilerSpan {
+    fn from(e: &LintError) -> CompilerSpan {
+        CompilerSpan::at(&e.position, e.source.name())
+    }
+}
 
-        let file_name = self.source.name();
+impl CompilerSpan {
+    /// A span over a position in the named file
+    fn at(p: &Position, file_name: &str) -> CompilerSpan {
+        // Highlights are 1-indexed bytes into the first line, running to its end for multi-line spans
+        let highlight_start = p.byte_start - p.line_byte_start + 1;
+        let highlight_end = match p.end_line == p.start_line {
+            true => p.byte_end - p.line_byte_start + 1,
+            false => p.line_text.len() + 1,
+        };
 
         CompilerSpan {
-            byte_end: byte_end,
-            byte_start: byte_start,
-            column_end: column_end,
-            column_start: column_start,
+            byte_end: p.byte_end,
+            byte_start: p.byte_start,
+            column_end: p.end_col,
+            column_start: p.start_col,
             expansion: None,
             file_name: file_name.to_string(),
             is_primary: true,
             label: None,
-            line_end: line_end,
-            line_start: line_start,
-            suggested_replacement: self.rule.replace.clone(),
+            line_end: p.end_line,
+            line_start: p.start_line,
+            suggested_replacement: None,
             suggestion_applicability: None,
             text: vec![CompilerSpanText {
-                highlight_end: highlight_end,
-                highlight_start: highlight_start,
-                text: self.line.0.to_string(),
+                highlight_end,
+                highlight_start,
+                text: p.line_text.clone(),
             }],
         }
     }
+
+    /// Attaches a suggested replacement to the span, with an optional label describing it
+    fn with_suggestion(mut self, suggestion: &Suggestion, label: Option<String>) -> Self {
+        self.suggested_replacement = Some(suggestion.replace.clone());
+        self.suggestion_applicability = Some(suggestion.applicability.clone());
+        self.label = label;
+        self
+    }
 }
 
 impl LintError {
+    /// A span over the whole match, which suggestions replace like `--fix` does
+    fn suggestion_span(&self) -> CompilerSpan {
+        CompilerSpan::at(&self.match_position(), self.source.name())
+    }
+
     pub fn json_diagnostic(&self) -> CompilerMessage {
-        let span: CompilerSpan = self.into();
+        let suggestions = self.suggestions();
+        let span = match suggestions.first() {
+            Some(s) => self.suggestion_span().with_suggestion(s, self.fix_title()),
+            None => CompilerSpan::from(self),
+        };
         let level = match self.rule.fails {
             true => CompilerMessageLevel::Error,
             false => CompilerMessageLevel::Warning,
@@ -215,20 +261,50 @@ impl LintError {
 
         let absolute_file_path = Path::new(&self.source.name())
             .canonicalize()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| self.source.name().to_string());
 
         let mut children = vec![CompilerMessageChild {
             children: vec![],
             code: None,
             level: level.clone(),
-            message: self.rule.clone().description,
+            message: self.description(),
             rendered: None,
             spans: vec![],
         }];
 
-        if let Some(help) = self.rule.help.clone() {
+        children.push(CompilerMessageChild {
+            children: vec![],
+            code: None,
+            level: CompilerMessageLevel::Note,
+            message: format!("Matched `{}`", self.matched()),
+            rendered: None,
+            spans: vec![],
+        });
+
+        if let Some(note) = &self.note {
+            children.push(CompilerMessageChild {
+                children: vec![],
+                code: None,
+                level: CompilerMessageLevel::Note,
+                message: note.0.clone(),
+                rendered: None,
+                spans: vec![],
+            });
+        }
+
+        if let Some(first) = &self.first_copy {
+            children.push(CompilerMessageChild {
+                children: vec![],
+                code: None,
+                level: CompilerMessageLevel::Note,
+                message: first.to_string(),
+                rendered: None,
+                spans: vec![CompilerSpan::at(&first.position, first.source.name())],
+            });
+        }
+
+        if let Some(help) = self.rule.help.as_deref().map(|h| self.interpolate(h)) {
             children.push(CompilerMessageChild {
                 children: vec![],
                 code: None,
@@ -239,6 +315,15 @@ impl LintError {
             });
         }
 
+        children.extend(suggestions.iter().skip(1).map(|s| CompilerMessageChild {
+            children: vec![],
+            code: None,
+            level: CompilerMessageLevel::Help,
+            message: format!("Try replacing with '{}'", s.replace),
+            rendered: None,
+            spans: vec![self.suggestion_span().with_suggestion(s, None)],
+        }));
+
         CompilerMessage {
             reason: CompilerMessageReason::CompilerMessage,
             package_id: String::new(),
@@ -272,3 +357,156 @@ impl LintError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_str, ty::Rules};
+
+    #[test]
+    fn spans_count_bytes_after_multibyte_characters() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            range = [0, 3]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        )
+        .unwrap();
+        let source = "// héllo wörld\nfn f() {\n    let s = \"é\"; x.unwrap();\n}\n";
+        let span: CompilerSpan = (&lint_str(source, "a.rs", &rules).unwrap()[0]).into();
+
+        assert_eq!(&source[span.byte_start..span.byte_end], ".unwrap()");
+        assert_eq!((span.line_start, span.line_end), (3, 3));
+        let text = &span.text[0];
+        assert_eq!(
+            &text.text[text.highlight_start - 1..text.highlight_end - 1],
+            ".unwrap()"
+        );
+    }
+
+    #[test]
+    fn suggestions_carry_their_applicability() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "Disallow Unwrap"
+            description = "No unwrap"
+            replace = '.expect("x")'
+            suggestions = [{ replace = "?", applicability = "MaybeIncorrect" }]
+            range = [1, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        )
+        .unwrap();
+        let source = "fn main() { y.unwrap(); }";
+        let d = lint_str(source, "a.rs", &rules).unwrap()[0].json_diagnostic();
+
+        let span = &d.message.spans[0];
+        assert_eq!(&source[span.byte_start..span.byte_end], ".unwrap()");
+        assert_eq!(
+            span.suggested_replacement.as_deref(),
+            Some(".expect(\"x\")")
+        );
+        assert_eq!(
+            span.suggestion_applicability,
+            Some(SuggestionApplicability::MachineApplicable)
+        );
+
+        let alternative = &d.message.children.last().unwrap().spans[0];
+        assert_eq!(
+            &source[alternative.byte_start..alternative.byte_end],
+            ".unwrap()"
+        );
+        assert_eq!(alternative.suggested_replacement.as_deref(), Some("?"));
+        assert_eq!(
+            alternative.suggestion_applicability,
+            Some(SuggestionApplicability::MaybeIncorrect)
+        );
+    }
+
+    #[test]
+    fn notes_the_matched_text() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.sum]
+            name = "sum"
+            description = "d"
+            range = [0, 2]
+            pattern = [["Ident", "a"], ["Punct", "+"], ["Ident", "b"]]
+            "#,
+        )
+        .unwrap();
+        let lints = lint_str("fn f() { let v = a + b; }", "a.rs", &rules).unwrap();
+        let json = serde_json::to_string(&lints[0].json_diagnostic()).unwrap();
+
+        assert!(json.contains(r#""message":"Matched `a + b`""#), "{json}");
+    }
+
+    #[test]
+    fn highlights_the_range_without_suggestions() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "Disallow Unwrap"
+            description = "No unwrap"
+            range = [1, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        )
+        .unwrap();
+        let source = "fn main() { y.unwrap(); }";
+        let d = lint_str(source, "a.rs", &rules).unwrap()[0].json_diagnostic();
+
+        let span = &d.message.spans[0];
+        assert_eq!(&source[span.byte_start..span.byte_end], "unwrap");
+        assert_eq!(span.suggested_replacement, None);
+    }
+
+    #[test]
+    fn fix_titles_label_the_suggestion() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "Disallow Unwrap"
+            description = "No unwrap"
+            replace = '.expect("x")'
+            fix_title = "Replace {1} with expect"
+            suggestions = [{ replace = "?" }]
+            range = [1, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        )
+        .unwrap();
+        let d = lint_str("fn main() { y.unwrap(); }", "a.rs", &rules).unwrap()[0].json_diagnostic();
+
+        assert_eq!(
+            d.message.spans[0].label.as_deref(),
+            Some("Replace unwrap with expect")
+        );
+        assert_eq!(d.message.children.last().unwrap().spans[0].label, None);
+    }
+
+    #[test]
+    fn highlights_crlf_sources_by_line_and_column() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "Disallow Unwrap"
+            description = "No unwrap"
+            range = [1, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        )
+        .unwrap();
+        let lint = &lint_str("fn main() {\r\n    y.unwrap();\r\n}\r\n", "a.rs", &rules).unwrap()[0];
+        let source = lint.source.inner();
+        let span = &lint.json_diagnostic().message.spans[0];
+
+        assert_eq!(&source[span.byte_start..span.byte_end], "unwrap");
+        assert_eq!((span.line_start, span.column_start), (2, 7));
+        assert_eq!((span.line_end, span.column_end), (2, 13));
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Range;
+
+use crate::{compiler::SuggestionApplicability, ty::LintError, BOM};
+
+/// A replacement of a byte range in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl LintError {
+    /// The first machine applicable edit suggested by this lint's rule, replacing the whole match,
+    /// if the rule is fixable
+    pub fn edit(&self) -> Option<Edit> {
+        if !self.rule.fixable {
+            return None;
+        }
+
+        let replacement = self
+            .suggestions()
+            .into_iter()
+            .find(|s| s.applicability == SuggestionApplicability::MachineApplicable)?
+            .replace;
+        let start = self.window.first()?.span().byte_range().start;
+        let end = self.window.last()?.span().byte_range().end;
+
+        Some(Edit {
+            range: start..end,
+            replacement,
+        })
+    }
+}
+
+/// Collects the edits for a file's lints in source order, dropping any that overlap an earlier one
+pub fn edits(errors: &[LintError]) -> Vec<Edit> {
+    let mut edits = errors.iter().filter_map(|e| e.edit()).collect::<Vec<_>>();
+    edits.sort_by_key(|e| (e.range.start, e.range.end));
+
+    let mut end = 0;
+    edits.retain(|e| {
+        let keep = e.range.start >= end;
+        if keep {
+            end = e.range.end;
+        }
+        keep
+    });
+
+    edits
+}
+
+/// Moves edits from a source as linted, without a byte order mark and with CRLFs read as LFs,
+/// onto the original text, so applying them leaves its line endings as they were
+pub fn to_original(original: &str, edits: &[Edit]) -> Vec<Edit> {
+    let bom = match original.starts_with(BOM) {
+        true => BOM.len_utf8(),
+        false => 0,
+    };
+
+    // Where each CRLF's `\r` was dropped, in the linted source
+    let dropped = original[bom..]
+        .match_indices("\r\n")
+        .enumerate()
+        .map(|(i, (p, _))| p - i)
+        .collect::<Vec<_>>();
+    let map = |n: usize| bom + n + dropped.partition_point(|p| *p < n);
+
+    edits
+        .iter()
+        .map(|e| Edit {
+            range: map(e.range.start)..map(e.range.end),
+            replacement: e.replacement.clone(),
+        })
+        .collect()
+}
+
+/// Applies ordered, non-overlapping edits to a source string
+pub fn apply(source: &str, edits: &[Edit]) -> String {
+    let mut out = source.to_string();
+    for e in edits.iter().rev() {
+        out.replace_range(e.range.clone(), &e.replacement);
+    }
+
+    out
+}
+
+/// Renders ordered, non-overlapping edits as a unified diff, with edits on shared lines in one hunk
+pub fn diff(file_name: &str, source: &str, edits: &[Edit]) -> String {
+    let mut hunks: Vec<(Range<usize>, Vec<&Edit>)> = Vec::new();
+    for e in edits {
+        let start = source[..e.range.start].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[e.range.end..]
+            .find('\n')
+            .map_or(source.len(), |i| e.range.end + i);
+
+        match hunks.last_mut() {
+            Some((lines, hunk)) if start <= lines.end => {
+                lines.end = end;
+                hunk.push(e);
+            }
+            _ => hunks.push((start..end, vec![e])),
+        }
+    }
+
+    let mut out = format!("--- {file_name}\n+++ {file_name}\n");
+    let mut offset = 0isize;
+    for (lines, hunk) in hunks {
+        let before = &source[lines.clone()];
+        let after = apply(
+            before,
+            &hunk
+                .iter()
+                .map(|e| Edit {
+                    range: e.range.start - lines.start..e.range.end - lines.start,
+                    replacement: e.replacement.clone(),
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let line = source[..lines.start].matches('\n').count() as isize + 1;
+        let (old, new) = (before.split('\n').count(), after.split('\n').count());
+        out.push_str(&format!("@@ -{line},{old} +{},{new} @@\n", line + offset));
+        before
+            .split('\n')
+            .for_each(|l| out.push_str(&format!("-{l}\n")));
+        after
+            .split('\n')
+            .for_each(|l| out.push_str(&format!("+{l}\n")));
+        offset += new as isize - old as isize;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_str, ty::Rules};
+
+    const SOURCE: &str = "fn f() {\n    a.unwrap();\n    b.unwrap();\n}\n";
+
+    fn expect(range: Range<usize>) -> Edit {
+        Edit {
+            range,
+            replacement: ".expect(\"...\")".to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_edits() {
+        assert_eq!(
+            apply(SOURCE, &[expect(14..23), expect(30..39)]),
+            "fn f() {\n    a.expect(\"...\");\n    b.expect(\"...\");\n}\n"
+        );
+    }
+
+    #[test]
+    fn diffs_edits_by_line() {
+        assert_eq!(
+            diff("a.rs", SOURCE, &[expect(14..23), expect(30..39)]),
+            "--- a.rs\n+++ a.rs\n\
+             @@ -2,1 +2,1 @@\n-    a.unwrap();\n+    a.expect(\"...\");\n\
+             @@ -3,1 +3,1 @@\n-    b.unwrap();\n+    b.expect(\"...\");\n"
+        );
+    }
+
+    #[test]
+    fn only_applies_machine_applicable_suggestions() {
+        let edited = |suggestions: &str| {
+            let rules: Rules = toml::from_str(&format!(
+                r#"
+                [rules.u]
+                name = "u"
+                description = "d"
+                suggestions = {suggestions}
+                fixable = true
+                range = [0, 3]
+                pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+                "#
+            ))
+            .unwrap();
+            let lints = lint_str(SOURCE, "a.rs", &rules).unwrap();
+            apply(SOURCE, &edits(&lints))
+        };
+
+        assert_eq!(
+            edited(r#"[{ replace = "?", applicability = "MaybeIncorrect" }]"#),
+            SOURCE
+        );
+        assert_eq!(
+            edited(
+                r#"[{ replace = "?", applicability = "MaybeIncorrect" },
+                    { replace = "!", applicability = "MachineApplicable" }]"#
+            ),
+            "fn f() {\n    a!;\n    b!;\n}\n"
+        );
+    }
+
+    #[test]
+    fn fills_replacements_from_named_groups() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.suffix]
+            name = "suffix"
+            description = "d"
+            replace = "${n}_${ty}"
+            fixable = true
+            range = [0, 0]
+            pattern = [["Literal", '/^(?P<n>\d+)(?P<ty>u32|i64)$/']]
+            "#,
+        )
+        .unwrap();
+        let source = "fn f() { g(5u32, 12i64, 7); }";
+        let lints = lint_str(source, "a.rs", &rules).unwrap();
+
+        assert_eq!(
+            apply(source, &edits(&lints)),
+            "fn f() { g(5_u32, 12_i64, 7); }"
+        );
+    }
+
+    fn fix(original: &str) -> String {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            replace = ".expect(\"a\nb\")"
+            fixable = true
+            range = [0, 3]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        )
+        .unwrap();
+        let lints = lint_str(original, "a.rs", &rules).unwrap();
+        apply(original, &to_original(original, &edits(&lints)))
+    }
+
+    #[test]
+    fn fixes_keep_each_line_ending() {
+        let original = "fn a() {\r\n    x.unwrap();\n}\r\nfn b() { y.unwrap(); }\n";
+        assert_eq!(
+            fix(original),
+            "fn a() {\r\n    x.expect(\"a\nb\");\n}\r\nfn b() { y.expect(\"a\nb\"); }\n"
+        );
+    }
+
+    #[test]
+    fn fixes_keep_a_byte_order_mark() {
+        let original = "\u{feff}fn a() {\r\n    x.unwrap();\r\n}\r\n";
+        assert_eq!(
+            fix(original),
+            "\u{feff}fn a() {\r\n    x.expect(\"a\nb\");\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn only_fixes_fixable_rules() {
+        let rules = |fixable: bool| -> Rules {
+            toml::from_str(&format!(
+                r#"
+                [rules.u]
+                name = "u"
+                description = "d"
+                replace = ".expect(\"...\")"
+                fixable = {fixable}
+                range = [0, 3]
+                pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+                "#
+            ))
+            .unwrap()
+        };
+
+        let hint = lint_str(SOURCE, "a.rs", &rules(false)).unwrap();
+        assert!(edits(&hint).is_empty());
+        assert_eq!(hint[0].suggestions()[0].replace, ".expect(\"...\")");
+
+        let fixable = lint_str(SOURCE, "a.rs", &rules(true)).unwrap();
+        assert_eq!(edits(&fixable), [expect(14..23), expect(30..39)]);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{compiler::CompilerSpan, ty::LintError};
+
+/// Escapes the message body of a workflow command
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property value of a workflow command
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl LintError {
+    /// Formats the lint as a GitHub Actions workflow command, so it's shown inline on PRs
+    pub fn github_annotation(&self) -> String {
+        let span: CompilerSpan = self.into();
+        let level = match self.fails {
+            true => "error",
+            false => "warning",
+        };
+
+        let mut message = self.to_string();
+        if let Some(help) = self.help() {
+            message = format!("{message}\n{help}");
+        }
+
+        format!(
+            "::{level} file={},line={},col={},endLine={},endColumn={},title={}::{}",
+            escape_property(&span.file_name),
+            span.line_start,
+            span.column_start,
+            span.line_end,
+            span.column_end,
+            escape_property(&self.rule.name),
+            escape_data(&message)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_data_and_properties() {
+        assert_eq!(escape_data("50%\r\n"), "50%25%0D%0A");
+        assert_eq!(escape_property("a:b,c"), "a%3Ab%2Cc");
+    }
+}
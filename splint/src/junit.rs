@@ -0,0 +1,99 @@
+use crate::{checkstyle::escape_attr, compiler::CompilerSpan, ty::LintError};
+
+/// Formats lints as a JUnit XML report, with a `testsuite` per linted file in order and a
+/// `testcase` per lint, failing for failing lints and passing with output for warnings
+pub fn junit(lints: &[LintError]) -> String {
+    let mut files: Vec<(&str, Vec<&LintError>)> = Vec::new();
+    for e in lints {
+        let name = e.source.name();
+        match files.iter_mut().find(|(f, _)| *f == name) {
+            Some((_, v)) => v.push(e),
+            None => files.push((name, vec![e])),
+        }
+    }
+
+    let fails = lints.iter().filter(|e| e.fails).count();
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"splint\" tests=\"{}\" failures=\"{fails}\">\n",
+        lints.len()
+    );
+    for (name, lints) in files {
+        let fails = lints.iter().filter(|e| e.fails).count();
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{fails}\">\n",
+            escape_attr(name),
+            lints.len()
+        ));
+        for e in lints {
+            let span: CompilerSpan = e.into();
+            let location = format!("{name}:{}:{}", span.line_start, span.column_start);
+            out.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\">\n",
+                escape_attr(&format!("{} at {location}", e.rule.name)),
+                escape_attr(name),
+            ));
+
+            let message = escape_attr(&e.to_string());
+            match e.fails {
+                true => out.push_str(&format!(
+                    "      <failure message=\"{message}\" type=\"{}\">{}</failure>\n",
+                    escape_attr(&e.rule.name),
+                    escape_attr(&format!("{location}: {}", e.matched())),
+                )),
+                false => out.push_str(&format!(
+                    "      <system-out>{}</system-out>\n",
+                    escape_attr(&format!("{location}: warning: {e}")),
+                )),
+            }
+            out.push_str("    </testcase>\n");
+        }
+        out.push_str("  </testsuite>\n");
+    }
+
+    out.push_str("</testsuites>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lint_str, ty::Rules};
+
+    #[test]
+    fn has_a_suite_per_file_and_a_case_per_lint() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "No <unwrap>"
+            fails = true
+            range = [0, 1]
+            pattern = [["Punct", "."], ["Ident", "unwrap"]]
+
+            [rules.e]
+            name = "e"
+            description = "No expect"
+            range = [0, 1]
+            pattern = [["Punct", "."], ["Ident", "expect"]]
+            "#,
+        )
+        .unwrap();
+        let mut lints = lint_str("fn f() {\n    a.unwrap();\n}", "a.rs", &rules).unwrap();
+        lints.extend(lint_str("fn f() { b.expect(); c.unwrap(); }", "b.rs", &rules).unwrap());
+        let xml = junit(&lints);
+
+        let suites = xml.split("<testsuite ").skip(1).collect::<Vec<_>>();
+        assert_eq!(suites.len(), 2);
+        assert!(suites[0].starts_with("name=\"a.rs\" tests=\"1\" failures=\"1\">"));
+        assert!(suites[1].starts_with("name=\"b.rs\" tests=\"2\" failures=\"1\">"));
+        assert_eq!(suites[1].matches("<testcase ").count(), 2);
+        assert_eq!(suites[1].matches("<system-out>").count(), 1);
+
+        assert!(xml.contains("<testsuites name=\"splint\" tests=\"3\" failures=\"2\">"));
+        assert!(xml.contains(
+            "<testcase name=\"u at a.rs:2:6\" classname=\"a.rs\">\n      \
+             <failure message=\"u: No &lt;unwrap&gt;\" type=\"u\">a.rs:2:6: .unwrap</failure>"
+        ));
+        assert!(xml.ends_with("</testsuites>"));
+    }
+}
@@ -1,78 +1,1517 @@
 #![allow(ambiguous_associated_items)]
 
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+
 use miette::NamedSource;
-use ty::{LintError, Named, Rule, Rules};
+use proc_macro2::{Delimiter, Group, Punct, Spacing, TokenStream, TokenTree};
+use profile::Profile;
+use ty::{
+    unescape_literal, FirstCopy, LintError, LintRunError, Named, Note, Options, Position, Rule,
+    Rules, Scope, TokenizeError, KEYWORDS,
+};
 
+pub mod baseline;
+pub mod checkstyle;
 pub mod compiler;
+pub mod fix;
+pub mod github;
+pub mod junit;
+pub mod profile;
+pub mod remote;
+pub mod serializable;
 pub mod ty;
 
 /// Finds all matches for a rule in any given source token list
-pub fn match_rule(rule: Rule, tokens: &Vec<Named>) -> Vec<(Rule, Vec<Named>)> {
-    let mut out = Vec::new();
-    if tokens.len() < rule.pattern.len() {
-        return out;
+pub fn match_rule(rule: Rule, tokens: &[Named]) -> Vec<(Rule, Vec<Named>)> {
+    rule.matches(tokens)
+        .into_iter()
+        .map(|m| (rule.clone(), m))
+        .collect()
+}
+
+/// Indexes token positions by kind, so rules can jump straight to candidate starts
+fn index_kinds(tokens: &[Named]) -> HashMap<&str, Vec<usize>> {
+    let mut index = HashMap::<&str, Vec<usize>>::new();
+    for (i, t) in tokens.iter().enumerate() {
+        index.entry(t.kind()).or_default().push(i);
+    }
+
+    index
+}
+
+/// Finds all matches for a rule, each with the tokens it repeats for `repeated_tokens` rules
+fn find_matches(
+    rule: &Rule,
+    tokens: &[Named],
+    index: &HashMap<&str, Vec<usize>>,
+    keep: &dyn Fn(&[Named]) -> bool,
+) -> Vec<(Vec<Named>, Option<Vec<Named>>)> {
+    match rule.repeated_tokens {
+        Some(_) => rule
+            .repeats(tokens)
+            .into_iter()
+            .filter(|(m, _)| keep(m))
+            .map(|(m, first)| (m, Some(first)))
+            .collect(),
+        None => match_indexed(rule, tokens, index, keep)
+            .into_iter()
+            .map(|m| (m, None))
+            .collect(),
+    }
+}
+
+/// Finds all matches for a rule using a token kind index,
+/// only trying starts where the first needle matches in full
+fn match_indexed(
+    rule: &Rule,
+    tokens: &[Named],
+    index: &HashMap<&str, Vec<usize>>,
+    keep: &dyn Fn(&[Named]) -> bool,
+) -> Vec<Vec<Named>> {
+    let first = &rule.pattern[0];
+    let mut candidates = first
+        .token_kinds()
+        .iter()
+        .filter_map(|k| index.get(k))
+        .flatten()
+        .copied()
+        .filter(|i| first.test(&tokens[*i]))
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+
+    rule.matches_from_where(tokens, candidates, keep)
+}
+
+/// Tests a set of rules against a source file
+pub fn test(rules: Rules, tokens: Vec<Named>, source: String, file_name: String) -> Vec<LintError> {
+    let mut errors = Vec::new();
+    test_with(rules, tokens, source, file_name, &mut |e| errors.push(e));
+    errors
+}
+
+/// A check that can't be written as a token pattern, e.g. on the length of functions
+pub trait CustomRule {
+    /// Finds lints in a file's tokens and source, e.g. with [`LintError::new`].
+    /// [`test_with_custom`] points them at the linted file, whatever file name they were given.
+    fn check(&self, tokens: &[Named], source: &str) -> Vec<LintError>;
+}
+
+/// Tests a set of rules and custom rules against a source file, reporting both in source order
+pub fn test_with_custom(
+    rules: Rules,
+    custom: &[Box<dyn CustomRule>],
+    tokens: Vec<Named>,
+    source: String,
+    file_name: String,
+) -> Vec<LintError> {
+    let named_source = NamedSource::new(&file_name, Arc::new(source.clone()));
+    let mut errors = custom
+        .iter()
+        .flat_map(|c| c.check(&tokens, &source))
+        .map(|mut e| {
+            e.source = named_source.clone();
+            e
+        })
+        .collect::<Vec<_>>();
+
+    test_with(rules, tokens, source, file_name, &mut |e| errors.push(e));
+    errors.sort_by_key(|e| {
+        let p = &e.position;
+        (
+            p.start_line,
+            p.start_col,
+            std::cmp::Reverse(e.rule.priority),
+        )
+    });
+    errors
+}
+
+/// Tests a set of rules against a source file, passing each lint to a callback as it's produced
+pub fn test_with(
+    rules: Rules,
+    tokens: Vec<Named>,
+    source: String,
+    file_name: String,
+    sink: &mut dyn FnMut(LintError),
+) {
+    test_profiled(
+        rules,
+        tokens,
+        source,
+        file_name,
+        sink,
+        &mut Profile::default(),
+    )
+}
+
+/// Tests a set of rules against a source file like [`test_with`], recording how long each rule took
+pub fn test_profiled(
+    rules: Rules,
+    tokens: Vec<Named>,
+    source: String,
+    file_name: String,
+    sink: &mut dyn FnMut(LintError),
+    profile: &mut Profile,
+) {
+    let max = rules.options.max_per_rule;
+    let lines = source.lines().collect::<Vec<_>>();
+    let line_lengths = lines.iter().map(|l| l.chars().count()).collect::<Vec<_>>();
+
+    let index = index_kinds(&tokens);
+    let mut any = rules
+        .rules
+        .values()
+        .flat_map(|v| {
+            let started = Instant::now();
+            let max_line = v.max_line_length.or(rules.options.max_line_length);
+            // Skipping tokens before matching would join up the tokens around them,
+            // so whole matches are rejected instead
+            let keep = |w: &[Named]| {
+                let short = |t: &Named| {
+                    max_line.is_none_or(|max_line| {
+                        line_lengths
+                            .get(t.span().start().line - 1)
+                            .is_none_or(|l| *l <= max_line)
+                    })
+                };
+                w.iter().all(short) && (!v.line_leading || leads_line(&w[0], &lines))
+            };
+            let mut matches = find_matches(v, &tokens, &index, &keep);
+            profile.record(&v.name, started.elapsed(), matches.len());
+            if let Some(min) = v.min_occurrences {
+                matches.drain(..min.saturating_sub(1).min(matches.len()));
+            }
+            let truncated = max.is_some_and(|n| matches.len() > n);
+            matches.truncate(max.unwrap_or(matches.len()));
+
+            let last = matches.len().saturating_sub(1);
+            matches
+                .into_iter()
+                .enumerate()
+                .map(move |(i, m)| ((v, m), truncated && i == last))
+        })
+        .collect::<Vec<_>>();
+
+    // Report in source order, higher priority rules first where highlights start together
+    any.sort_by_key(|((n, (r, _)), _)| {
+        let start = r[*n.range.start()].span().start();
+        (start.line, start.column, std::cmp::Reverse(n.priority))
+    });
+
+    // Rules that only differ by name (e.g. from merged configs) report the same window once
+    let mut seen = HashSet::new();
+    let any = any
+        .into_iter()
+        .filter(|((n, (r, _)), _)| {
+            seen.insert((
+                r.first().unwrap().span().byte_range().start,
+                r.last().unwrap().span().byte_range().end,
+                n.range.clone(),
+                n.description.clone(),
+                n.help.clone(),
+                n.fails,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let starts = line_starts(&source);
+    let named_source = NamedSource::new(&file_name, Arc::new(source.clone()));
+
+    for ((n, (r, first)), truncated) in any {
+        let mut position = Position::new(&r[n.range.clone()], &lines, &starts);
+        if let Some(chars) = n.range_chars {
+            position = position.narrow(chars, &source, &lines, &starts);
+        }
+
+        let mut e = LintError {
+            window: r.clone(),
+            fails: n.fails,
+            truncated,
+            rule: n.clone(),
+            position,
+            source: named_source.clone(),
+            note: None,
+            first_copy: first.map(|f| FirstCopy {
+                position: Position::new(&f[n.range.clone()], &lines, &starts),
+                source: named_source.clone(),
+            }),
+        };
+        e.note = n.note.as_deref().map(|v| Note(e.interpolate(v)));
+        sink(e);
     }
+}
+
+/// Whether only whitespace comes before a token on its line
+fn leads_line(token: &Named, lines: &[&str]) -> bool {
+    let start = token.span().start();
+    lines
+        .get(start.line - 1)
+        .is_some_and(|l| l.chars().take(start.column).all(char::is_whitespace))
+}
+
+/// Finds the byte offset of the start of each line, including preceding line endings
+pub(crate) fn line_starts(source: &str) -> Vec<usize> {
+    source
+        .split_inclusive('\n')
+        .scan(0, |start, line| {
+            let current = *start;
+            *start += line.len();
+            Some(current)
+        })
+        .collect()
+}
 
-    match rule.test(tokens) {
-        Ok(_) => {}
-        Err(e) => {
-            out.push((rule.clone(), e.clone()));
+/// Reads, tokenizes and lints a source file
+pub fn lint(loc: &str, rules: &Rules) -> Result<Vec<LintError>, LintRunError> {
+    lint_profiled(loc, rules, &mut Profile::default())
+}
+
+/// Reads, tokenizes and lints a source file, recording how long each rule took
+pub fn lint_profiled(
+    loc: &str,
+    rules: &Rules,
+    profile: &mut Profile,
+) -> Result<Vec<LintError>, LintRunError> {
+    let input = fs::read_to_string(loc).map_err(|error| LintRunError::Io {
+        file: loc.to_string(),
+        error,
+    })?;
+    lint_str_profiled(&input, loc, rules, profile)
+}
+
+/// The UTF-8 byte order mark some Windows editors save at the start of files
+pub const BOM: char = '\u{feff}';
+
+/// Tokenizes and lints an in-memory source string.
+/// Each source is tokenized on its own, so a pattern never matches across two files.
+pub fn lint_str(
+    source: &str,
+    file_name: &str,
+    rules: &Rules,
+) -> Result<Vec<LintError>, LintRunError> {
+    lint_str_profiled(source, file_name, rules, &mut Profile::default())
+}
+
+/// Tokenizes and lints an in-memory source string, recording how long each rule took
+pub fn lint_str_profiled(
+    source: &str,
+    file_name: &str,
+    rules: &Rules,
+    profile: &mut Profile,
+) -> Result<Vec<LintError>, LintRunError> {
+    // Positions are relative to after any byte order mark, which would shift the first line
+    let source = source.strip_prefix(BOM).unwrap_or(source);
+    // Like rustc, read CRLF line endings as LF, so literals hold the same text on every platform
+    let source = match source.contains("\r\n") {
+        true => Cow::Owned(source.replace("\r\n", "\n")),
+        false => Cow::Borrowed(source),
+    };
+    let source = source.as_ref();
+    let named = tokenize_with(source, &rules.options).map_err(|e| LintRunError::Parse {
+        file: file_name.to_string(),
+        message: format!("{e} at line {}, column {}", e.line, e.column),
+    })?;
+
+    let mut rules = rules.clone();
+    rules.rules.retain(|_, r| r.applies_to(file_name));
+    file_ignores(&mut rules, source);
 
-            let line = e.last().unwrap().span().end().line;
-            let col = e.last().unwrap().span().end().column;
+    let mut errors = Vec::new();
+    test_profiled(
+        rules,
+        named,
+        source.to_string(),
+        file_name.to_string(),
+        &mut |e| errors.push(e),
+        profile,
+    );
+    Ok(errors)
+}
+
+/// Tokenizes source text with the default options, e.g. to call [`match_rule`] directly
+pub fn tokenize(source: &str) -> Result<Vec<Named>, TokenizeError> {
+    tokenize_with(source, &Options::default())
+}
+
+/// Tokenizes source text with the given options, ignoring any leading `#!` interpreter line
+pub fn tokenize_with(source: &str, options: &Options) -> Result<Vec<Named>, TokenizeError> {
+    let stream = TokenStream::from_str(&blank_shebang(source)).map_err(|e| {
+        let start = e.span().start();
+        TokenizeError {
+            message: e.to_string(),
+            line: start.line,
+            column: start.column + 1,
+        }
+    })?;
+
+    let mut tokens = parse(stream, options);
+    if options.blank_lines {
+        tokens.extend(blank_lines(source, &tokens));
+        tokens.sort_by_key(|t| t.span().byte_range().start);
+    }
 
-            let more = tokens
+    Ok(tokens)
+}
+
+/// `BlankLine` tokens for the whitespace-only lines of a source, other than those inside a token
+/// such as a multi-line string
+fn blank_lines(source: &str, tokens: &[Named]) -> Vec<Named> {
+    let inside = |byte: usize| {
+        let i = tokens.partition_point(|t| t.span().byte_range().start <= byte);
+        i > 0 && tokens[i - 1].span().byte_range().end > byte
+    };
+
+    source
+        .lines()
+        .zip(line_starts(source))
+        .enumerate()
+        .filter(|(_, (line, start))| line.trim().is_empty() && !inside(*start))
+        .map(|(i, (line, start))| Named::blank_line(i + 1, line, start))
+        .collect()
+}
+
+/// Tokenizes and lints the ```` ```rust ```` code blocks of a markdown file,
+/// with positions in the markdown file itself
+pub fn lint_markdown(
+    source: &str,
+    file_name: &str,
+    rules: &Rules,
+) -> Result<Vec<LintError>, LintRunError> {
+    lint_markdown_profiled(source, file_name, rules, &mut Profile::default())
+}
+
+/// Tokenizes and lints the Rust code blocks of a markdown file, recording how long each rule took
+pub fn lint_markdown_profiled(
+    source: &str,
+    file_name: &str,
+    rules: &Rules,
+    profile: &mut Profile,
+) -> Result<Vec<LintError>, LintRunError> {
+    let source = source
+        .strip_prefix(BOM)
+        .unwrap_or(source)
+        .replace("\r\n", "\n");
+    let named_source = NamedSource::new(file_name, Arc::new(source.clone()));
+
+    let mut errors = Vec::new();
+    for (block, lines) in rust_blocks(&source) {
+        for mut e in lint_str_profiled(&block, file_name, rules, profile)? {
+            // Blanked out markdown reads as `BlankLine` tokens, which aren't part of the block
+            let within = |t: &Named| lines.contains(&t.span().start().line);
+            if e.window.iter().all(within) {
+                e.source = named_source.clone();
+                errors.push(e);
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
+/// Each ```` ```rust ```` code block of a markdown file, as a copy of the file with everything
+/// else blanked out, so lines, columns and byte offsets stay those of the markdown.
+/// Blocks come with the 1-indexed lines they cover.
+fn rust_blocks(markdown: &str) -> Vec<(String, RangeInclusive<usize>)> {
+    let blank = |line: &str| {
+        let text = line.trim_end_matches('\n');
+        " ".repeat(text.len()) + &line[text.len()..]
+    };
+    let mut blocks = Vec::new();
+    let mut current: Option<usize> = None;
+
+    let lines = markdown.split_inclusive('\n').collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+
+        match current.take() {
+            Some(start) => blocks.push(start..i),
+            None => {
+                let lang = info.trim().split([',', ' ']).next().unwrap_or_default();
+                if lang == "rust" {
+                    current = Some(i + 1);
+                }
+            }
+        }
+    }
+
+    // Like an unclosed fence in rendered markdown, a block left open runs to the end of the file
+    blocks.extend(current.map(|start| start..lines.len()));
+    blocks
+        .into_iter()
+        .map(|block| {
+            let text = lines
                 .iter()
-                .skip_while(|v| {
-                    let span_line = v.span().start().line;
-                    if span_line < line {
-                        return true;
+                .enumerate()
+                .map(|(i, line)| match block.contains(&i) {
+                    true => line.to_string(),
+                    false => blank(line),
+                })
+                .collect();
+            (text, block.start + 1..=block.end)
+        })
+        .collect()
+}
+
+/// Blanks out a leading `#!` interpreter line, which isn't Rust, keeping byte offsets
+/// (and so line numbers) the same. `#![...]` inner attributes are left alone.
+fn blank_shebang(source: &str) -> Cow<'_, str> {
+    let Some(rest) = source.strip_prefix("#!") else {
+        return Cow::Borrowed(source);
+    };
+    if rest.trim_start().starts_with('[') {
+        return Cow::Borrowed(source);
+    }
+
+    let end = source.find('\n').unwrap_or(source.len());
+    Cow::Owned(" ".repeat(end) + &source[end..])
+}
+
+/// Keywords that introduce items, which name a following brace block over other keywords
+const ITEM_KEYWORDS: [&str; 7] = ["fn", "impl", "mod", "trait", "struct", "enum", "union"];
+
+/// Keywords that introduce blocks within expressions
+const BLOCK_KEYWORDS: [&str; 7] = ["loop", "for", "while", "if", "else", "match", "unsafe"];
+
+/// Multi-character operators joined into `Op` tokens, longest first
+const OPERATORS: [&str; 24] = [
+    "..=", "...", "<<=", ">>=", "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>", "..",
+];
+
+/// Drops rules disabled for the whole file by `// splint-ignore-file: <names>`,
+/// or every rule for a bare `// splint-ignore-file` or `all`
+fn file_ignores(rules: &mut Rules, source: &str) {
+    for directive in source
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("// splint-ignore-file"))
+    {
+        let names = match directive.trim().strip_prefix(':') {
+            Some(names) => names.split(',').map(str::trim).collect::<Vec<_>>(),
+            None if directive.trim().is_empty() => vec!["all"],
+            None => continue,
+        };
+
+        match names.contains(&"all") {
+            true => rules.rules.clear(),
+            false => rules
+                .rules
+                .retain(|k, r| !names.contains(&k.as_str()) && !names.contains(&r.name.as_str())),
+        }
+    }
+}
+
+/// Flattens a token stream into a list of named tokens, with groups surrounded by their delimiters
+pub fn parse(stream: TokenStream, options: &Options) -> Vec<Named> {
+    parse_scoped(stream, options, None)
+}
+
+/// Flattens a token stream sitting in the given brace block, tracking the blocks it opens
+fn parse_scoped(stream: TokenStream, options: &Options, scope: Option<Arc<Scope>>) -> Vec<Named> {
+    let mut out = Vec::new();
+    let mut trees = stream.into_iter().peekable();
+    let mut intro: Option<String> = None;
+
+    while let Some(tt) = trees.next() {
+        match tt {
+            TokenTree::Punct(p) if p.as_char() == '#' && (options.attributes || options.docs) => {
+                // Look past the `!` of an inner attribute, only consuming it if an attribute follows
+                let mut ahead = trees.clone();
+                let inner = matches!(ahead.peek(), Some(TokenTree::Punct(b)) if b.as_char() == '!');
+                if inner {
+                    ahead.next();
+                }
+
+                let attr = match ahead.peek() {
+                    Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+                        attribute(&p, g, inner, options, &scope)
                     }
+                    _ => None,
+                };
 
-                    if span_line == line {
-                        return v.span().start().column < col;
+                match attr {
+                    Some(attr) => {
+                        ahead.next();
+                        trees = ahead;
+                        out.extend(attr);
                     }
+                    None => out.push(Named::from(p).with_scope(scope.clone())),
+                }
+            }
+            TokenTree::Punct(p) if p.as_char() == '\'' && options.lifetimes => match trees.peek() {
+                Some(TokenTree::Ident(i)) => {
+                    let span = p.span().join(i.span()).unwrap_or(p.span());
+                    out.push(Named::new("Lifetime", i.to_string(), span).with_scope(scope.clone()));
+                    trees.next();
+                }
+                _ => out.push(Named::from(p).with_scope(scope.clone())),
+            },
+            TokenTree::Punct(p) if p.spacing() == Spacing::Joint && options.operators => {
+                // Puncts joined to the next only form an operator when they spell a known one
+                let mut text = p.as_char().to_string();
+                let mut ahead = trees.clone();
+                let mut last = p.clone();
+                while last.spacing() == Spacing::Joint {
+                    let Some(TokenTree::Punct(n)) = ahead.next() else {
+                        break;
+                    };
+                    text.push(n.as_char());
+                    last = n;
+                }
 
-                    false
-                })
-                .map(|v| v.clone());
+                match OPERATORS.iter().find(|o| text.starts_with(*o)) {
+                    Some(op) => {
+                        let mut end = p.span();
+                        for _ in 1..op.len() {
+                            if let Some(TokenTree::Punct(n)) = trees.next() {
+                                end = n.span();
+                            }
+                        }
+
+                        let span = p.span().join(end).unwrap_or(p.span());
+                        out.push(Named::new("Op", op.to_string(), span).with_scope(scope.clone()));
+                    }
+                    None => out.push(Named::from(p).with_scope(scope.clone())),
+                }
+            }
+            TokenTree::Group(g) => {
+                let inner = match (macro_name(&out), g.delimiter()) {
+                    (Some(name), _) => Some(Arc::new(Scope {
+                        kind: Some(name),
+                        parent: scope.clone(),
+                    })),
+                    (None, Delimiter::Brace) => Some(Arc::new(Scope {
+                        kind: intro.take(),
+                        parent: scope.clone(),
+                    })),
+                    (None, _) => scope.clone(),
+                };
+
+                let delim = Named::delim_pair(g.delimiter(), g.span_open(), g.span_close());
+                out.push(delim[0].clone().with_scope(scope.clone()));
+                out.extend(parse_scoped(g.stream(), options, inner));
+                out.push(delim[1].clone().with_scope(scope.clone()));
+            }
+            _ => {
+                match &tt {
+                    TokenTree::Punct(p) if p.as_char() == ';' => intro = None,
+                    TokenTree::Ident(i) => {
+                        let i = i.to_string();
+                        let item = intro.as_deref().is_some_and(|k| ITEM_KEYWORDS.contains(&k));
+                        if ITEM_KEYWORDS.contains(&i.as_str()) && !item
+                            || BLOCK_KEYWORDS.contains(&i.as_str()) && intro.is_none()
+                        {
+                            intro = Some(i);
+                        }
+                    }
+                    _ => {}
+                }
 
-            out.extend(match_rule(rule.clone(), &more.clone().collect()));
+                out.push(Named::from(tt).with_scope(scope.clone()));
+            }
         }
     }
 
     out
 }
 
-/// Tests a set of rules against a source file
-pub fn test(rules: Rules, tokens: Vec<Named>, source: String, file_name: String) -> Vec<LintError> {
-    let any = rules
-        .rules
+/// The name of the macro called by the last tokens, e.g. `println!`, if they're a macro call
+fn macro_name(out: &[Named]) -> Option<String> {
+    match out {
+        [.., name, bang]
+            if name.kind() == "Ident"
+                && !KEYWORDS.contains(&name.value())
+                && bang.kind() == "Punct"
+                && bang.value() == "!" =>
+        {
+            Some(format!("{}!", name.value()))
+        }
+        _ => None,
+    }
+}
+
+/// Reconstructs an attribute into an `Attr` (or `InnerAttr` for `#![...]`) token followed by
+/// its arguments, or a `Doc` token for doc comments
+fn attribute(
+    pound: &Punct,
+    group: &Group,
+    inner: bool,
+    options: &Options,
+    scope: &Option<Arc<Scope>>,
+) -> Option<Vec<Named>> {
+    let tokens = group.stream().into_iter().collect::<Vec<_>>();
+
+    let mut path = String::new();
+    let mut end = pound.span();
+    let rest = tokens
         .iter()
-        .flat_map(|(_, v)| match_rule(v.clone(), &tokens))
+        .skip_while(|tt| match tt {
+            TokenTree::Ident(i) => {
+                path.push_str(&i.to_string());
+                end = i.span();
+                true
+            }
+            TokenTree::Punct(p) if p.as_char() == ':' => {
+                path.push(':');
+                true
+            }
+            _ => false,
+        })
+        .cloned()
         .collect::<Vec<_>>();
 
-    let errors = any.iter().map(|(n, r)| LintError {
-        window: r.clone(),
-        fails: n.fails,
-        rule: n.clone(),
-        line: (
-            source
-                .lines()
-                .nth(r.clone().first().unwrap().span().start().line - 1)
-                .unwrap_or_default()
-                .to_string(),
-            // find chars before line
-            source
-                .lines()
-                .enumerate()
-                .filter(|(i, _)| *i < r.clone().first().unwrap().span().start().line - 1)
-                .map(|(_, l)| l.chars().count())
-                .reduce(|a, b| a + b)
-                .unwrap_or_default(),
-        ),
-        source: NamedSource::new(&file_name, source.clone()),
-    });
+    if path == "doc" && options.docs {
+        if let [TokenTree::Punct(eq), TokenTree::Literal(lit)] = rest.as_slice() {
+            if eq.as_char() == '=' {
+                let text = unescape_literal(&lit.to_string())?;
+                return Some(vec![
+                    Named::new("Doc", text, lit.span()).with_scope(scope.clone())
+                ]);
+            }
+        }
+    }
+
+    if !options.attributes || path.is_empty() {
+        return None;
+    }
+
+    let span = pound.span().join(end).unwrap_or(pound.span());
+    let kind = match inner {
+        true => "InnerAttr",
+        false => "Attr",
+    };
+    let mut out = vec![Named::new(kind, path, span).with_scope(scope.clone())];
+    out.extend(parse_scoped(
+        rest.into_iter().collect(),
+        options,
+        scope.clone(),
+    ));
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> Rules {
+        toml::from_str(toml).unwrap()
+    }
+
+    const UNWRAP: &str = r#"
+        [rules.u]
+        name = "u"
+        description = "d"
+        range = [0, 3]
+        pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+        "#;
 
-    errors.collect::<Vec<_>>()
+    #[test]
+    fn lints_a_string() {
+        let lints = lint_str("fn f() { a.unwrap(); }", "a.rs", &rules(UNWRAP)).unwrap();
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].rule.name, "u");
+        assert!(
+            lint_str("fn f() { a.unwrap_or(1); }", "a.rs", &rules(UNWRAP))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// Where each match starts, with its tokens, as tokens can't be compared directly
+    fn located(windows: &[Vec<Named>]) -> Vec<(usize, usize, String)> {
+        windows
+            .iter()
+            .map(|w| {
+                let start = w[0].span().start();
+                (start.line, start.column, format!("{w:?}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn match_rule_agrees_with_linting_files() {
+        let r = rules(UNWRAP);
+        let source = "fn f() {\n    a.unwrap();\n    b.c().unwrap();\n    d.unwrap_or(1);\n}\n";
+        let tokens = parse(TokenStream::from_str(source).unwrap(), &r.options);
+        let path = std::env::temp_dir().join(format!("splint-lib-{}.rs", std::process::id()));
+        fs::write(&path, source).unwrap();
+
+        let matched = match_rule(r.rules["u"].clone(), &tokens)
+            .into_iter()
+            .map(|(_, w)| w)
+            .collect::<Vec<_>>();
+        let windows =
+            |lints: Vec<LintError>| lints.into_iter().map(|e| e.window).collect::<Vec<_>>();
+        let from_file = windows(lint(path.to_str().unwrap(), &r).unwrap());
+        let from_str = windows(lint_str(source, "a.rs", &r).unwrap());
+
+        assert_eq!(matched.len(), 2);
+        assert_eq!(located(&matched), located(&from_file));
+        assert_eq!(located(&matched), located(&from_str));
+    }
+
+    #[test]
+    fn matches_attributes_and_doc_comments() {
+        let r = rules(
+            r#"
+            [options]
+            attributes = true
+            docs = true
+
+            [rules.derive]
+            name = "derive"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Attr", "derive"]]
+
+            [rules.todo]
+            name = "todo"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Doc", " TODO: explain"]]
+            "#,
+        );
+        let source = "/// TODO: explain\n#[derive(Debug)]\n#[allow(unused)]\nstruct A;\n";
+        let mut names = lint_str(source, "a.rs", &r)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.rule.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["derive", "todo"]);
+    }
+
+    #[test]
+    fn caps_matches_per_rule() {
+        let mut r = rules(UNWRAP);
+        r.options.max_per_rule = Some(2);
+        let lints = lint_str("fn f() { a.unwrap(); b.unwrap(); c.unwrap(); }", "a.rs", &r).unwrap();
+
+        assert_eq!(lints.len(), 2);
+        assert_eq!(lints[0].to_string(), "u: d");
+        assert_eq!(lints[1].to_string(), "u: d (truncated)");
+    }
+
+    #[test]
+    fn reports_unreadable_and_unparseable_files() {
+        let r = rules(UNWRAP);
+
+        let missing = lint("does/not/exist.rs", &r).unwrap_err();
+        assert!(matches!(&missing, LintRunError::Io { file, .. } if file == "does/not/exist.rs"));
+
+        let broken = lint_str("fn f() {", "a.rs", &r).unwrap_err();
+        assert!(matches!(&broken, LintRunError::Parse { file, .. } if file == "a.rs"));
+        assert!(broken.to_string().starts_with("Couldn't parse a.rs"));
+    }
+
+    #[test]
+    fn skips_matches_on_long_lines() {
+        let r = rules(
+            r#"
+            [options]
+            max_line_length = 20
+
+            [rules.ab]
+            name = "ab"
+            description = "alpha then beta"
+            range = [0, 1]
+            pattern = [["Ident", "alpha"], ["Ident", "beta"]]
+            "#,
+        );
+        let count = |source: &str| lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("alpha beta\n"), 1);
+        assert_eq!(count("alpha beta; let long_line_here = 1;\n"), 0);
+        assert_eq!(count("alpha\nbeta\nalpha beta\n"), 2);
+        // Tokens on a skipped line don't join up the ones around it
+        assert_eq!(count("alpha\nlet long_line_here = 12345;\nbeta\n"), 0);
+    }
+
+    #[test]
+    fn matches_tens_of_thousands_of_times() {
+        let source = format!("fn f() {{\n{}}}\n", "x.unwrap();\n".repeat(30_000));
+        assert_eq!(
+            lint_str(&source, "a.rs", &rules(UNWRAP)).unwrap().len(),
+            30_000
+        );
+    }
+
+    #[test]
+    fn reports_from_the_minimum_occurrence() {
+        let mut r = rules(UNWRAP);
+        r.rules.get_mut("u").unwrap().min_occurrences = Some(3);
+        let count = |source: &str| lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { a.unwrap(); b.unwrap(); }"), 0);
+        assert_eq!(count("fn f() { a.unwrap(); b.unwrap(); c.unwrap(); }"), 1);
+        assert_eq!(
+            count("fn f() { a.unwrap(); b.unwrap(); c.unwrap(); d.unwrap(); }"),
+            2
+        );
+    }
+
+    #[test]
+    fn scoped_rules_only_match_inside_their_blocks() {
+        let r = rules(
+            r#"
+            [rules.clone]
+            name = "clone"
+            description = "d"
+            scope = "fn"
+            range = [0, 2]
+            pattern = [["Ident", "clone"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        );
+        let lines = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window[0].span().start().line)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(lines("static A: B = B.clone();\nmod m { const C: D = D.clone(); }\n").is_empty());
+        assert_eq!(
+            lines("static A: B = B.clone();\nfn f() {\n    if x { a.clone(); }\n}\n"),
+            [3]
+        );
+        assert_eq!(
+            lines("impl A {\n    fn f(&self) { self.clone(); }\n}\n"),
+            [2]
+        );
+    }
+
+    #[test]
+    fn reports_identical_rules_once() {
+        let r = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "No foo"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+
+            [rules.b]
+            name = "b"
+            description = "No foo"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+
+            [rules.c]
+            name = "c"
+            description = "Something else about foo"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+            "#,
+        );
+        let lints = lint_str("fn f() { foo; }", "a.rs", &r).unwrap();
+
+        assert_eq!(lints.len(), 2);
+        assert_eq!(
+            lints
+                .iter()
+                .filter(|e| e.rule.description == "No foo")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn indexed_matching_agrees_with_a_full_scan() {
+        let r = rules(
+            r#"
+            [rules.semi]
+            name = "semi"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "a"], ["Punct", ";"]]
+
+            [rules.stmt]
+            name = "stmt"
+            description = "d"
+            range = [0, 0]
+            pattern = [["StmtEnd", ";"]]
+
+            [rules.regex]
+            name = "regex"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "/^[ab]$/"]]
+            "#,
+        );
+        let source = "fn f() { a; b + 1; let c = a.b; { 2 } }";
+        let tokens = parse(TokenStream::from_str(source).unwrap(), &r.options);
+        let index = index_kinds(&tokens);
+
+        for rule in r.rules.values() {
+            // Twice, so the second run goes through the cached regexes
+            for _ in 0..2 {
+                assert_eq!(
+                    located(&match_indexed(rule, &tokens, &index, &|_| true)),
+                    located(
+                        &match_rule(rule.clone(), &tokens)
+                            .into_iter()
+                            .map(|(_, m)| m)
+                            .collect::<Vec<_>>()
+                    ),
+                    "{}",
+                    rule.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_rules_for_a_whole_file() {
+        let r = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+
+            [rules.b]
+            name = "b"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "bar"]]
+            "#,
+        );
+        let names = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.rule.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names("// splint-ignore-file: a\nfn f() { foo; bar; }"),
+            ["b"]
+        );
+        assert!(names("// splint-ignore-file\nfn f() { foo; bar; }").is_empty());
+        assert!(names("// splint-ignore-file: all\nfn f() { foo; bar; }").is_empty());
+    }
+
+    #[test]
+    fn positions_skip_byte_order_marks_and_carriage_returns() {
+        let locate = |source: &str| {
+            let p = &lint_str(source, "a.rs", &rules(UNWRAP)).unwrap()[0].position;
+            (p.start_line, p.start_col, p.line_text.clone())
+        };
+
+        assert_eq!(
+            locate("\u{feff}fn f() {\n    a.unwrap();\n}"),
+            (2, 6, "    a.unwrap();".to_string())
+        );
+        assert_eq!(
+            locate("fn f() {\r\n    let b = 1;\r\n    a.unwrap();\r\n}\r\n"),
+            (3, 6, "    a.unwrap();".to_string())
+        );
+    }
+
+    #[test]
+    fn callbacks_receive_lints_in_vector_order() {
+        let r = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [rules.b]
+            name = "b"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "b"]]
+            "#,
+        );
+        let source = "fn f() { a; b; a; b; b; }";
+        let tokens = parse(source.parse().unwrap(), &r.options);
+        let key = |e: &LintError| (e.rule.name.clone(), e.position.byte_start);
+
+        let collected = test(r.clone(), tokens.clone(), source.into(), "a.rs".into());
+        let mut streamed = Vec::new();
+        test_with(r, tokens, source.into(), "a.rs".into(), &mut |e| {
+            streamed.push(key(&e))
+        });
+
+        assert_eq!(collected.len(), 5);
+        assert_eq!(collected.iter().map(key).collect::<Vec<_>>(), streamed);
+    }
+
+    #[test]
+    fn line_leading_only_constrains_the_first_token() {
+        let r = rules(
+            r#"
+            [rules.pf]
+            name = "pf"
+            description = "pub fn at the start of a line"
+            range = [0, 1]
+            line_leading = true
+            pattern = [["Ident", "pub"], { kind = "Ident" }]
+            "#,
+        );
+        let matched = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(LintError::matched)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matched("pub fn foo() {}\n    pub fn bar() {}\n"),
+            ["pub fn", "pub fn"]
+        );
+        assert!(matched("struct A; pub fn foo() {}\n").is_empty());
+        // `struct` leads its line, but `pub` doesn't
+        assert!(matched("fn a() {} pub\nstruct A;\n").is_empty());
+    }
+
+    #[test]
+    fn lints_files_with_shebangs() {
+        let source = "#!/usr/bin/env rust-script\nfn main() {\n    a.unwrap();\n}\n";
+        let lints = lint_str(source, "a.rs", &rules(UNWRAP)).unwrap();
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].position.start_line, 3);
+        assert_eq!(
+            lints[0].position.byte_start,
+            source.find(".unwrap").unwrap()
+        );
+        assert!(lint_str(
+            "#![allow(unused)]\nfn f() { a.unwrap(); }",
+            "a.rs",
+            &rules(UNWRAP)
+        )
+        .is_ok_and(|l| l.len() == 1));
+    }
+
+    #[test]
+    fn matches_specific_and_any_lifetimes() {
+        let r = rules(
+            r#"
+            [options]
+            lifetimes = true
+
+            [rules.static]
+            name = "static"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Punct", "&"], ["Lifetime", "static"]]
+
+            [rules.any]
+            name = "any"
+            description = "d"
+            range = [0, 0]
+            pattern = [{ kind = "Lifetime" }]
+            "#,
+        );
+        let source = "fn f<'a>(x: &'a str) -> &'static str { x }";
+        let mut lints = lint_str(source, "a.rs", &r)
+            .unwrap()
+            .iter()
+            .map(|e| (e.rule.name.clone(), e.matched()))
+            .collect::<Vec<_>>();
+        lints.sort();
+
+        assert_eq!(
+            lints,
+            [
+                ("any".to_string(), "'a".to_string()),
+                ("any".to_string(), "'a".to_string()),
+                ("any".to_string(), "'static".to_string()),
+                ("static".to_string(), "&'static".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_rules_in_definition_order() {
+        let toml = r#"
+            [rules.zeta]
+            name = "zeta"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [rules.alpha]
+            name = "alpha"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+            help = "differs from zeta"
+
+            [rules.mid]
+            name = "mid"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "b"]]
+            "#;
+        let names = || {
+            lint_str("fn f() { b; a; }", "a.rs", &rules(toml))
+                .unwrap()
+                .into_iter()
+                .map(|e| e.rule.name)
+                .collect::<Vec<_>>()
+        };
+
+        // Source order first, then definition order for lints at the same place
+        assert_eq!(names(), ["mid", "zeta", "alpha"]);
+        assert_eq!(names(), names());
+    }
+
+    #[test]
+    fn tells_inner_attributes_from_outer_ones() {
+        let r = rules(
+            r#"
+            [options]
+            attributes = true
+
+            [rules.inner]
+            name = "inner"
+            description = "d"
+            range = [0, 0]
+            pattern = [{ kind = "InnerAttr" }]
+
+            [rules.outer]
+            name = "outer"
+            description = "d"
+            range = [0, 0]
+            pattern = [{ kind = "Attr" }]
+            "#,
+        );
+        let source = "#![no_std]\n#[derive(Debug)]\nstruct A;\nfn f() { !x; }";
+        let lints = lint_str(source, "a.rs", &r)
+            .unwrap()
+            .iter()
+            .map(|e| (e.rule.name.clone(), e.window[0].value().to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lints,
+            [
+                ("inner".to_string(), "no_std".to_string()),
+                ("outer".to_string(), "derive".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_macros_ignores_macro_arguments() {
+        let toml = |skip: bool| {
+            rules(&format!(
+                r#"
+                [rules.u]
+                name = "u"
+                description = "d"
+                skip_macros = {skip}
+                range = [0, 3]
+                pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+                "#
+            ))
+        };
+        let source = "fn f() {\n    println!(\"{}\", vec![a.unwrap()]);\n    b.unwrap();\n}";
+        let lines = |r: &Rules| {
+            lint_str(source, "a.rs", r)
+                .unwrap()
+                .iter()
+                .map(|e| e.position.start_line)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines(&toml(false)), [2, 3]);
+        assert_eq!(lines(&toml(true)), [3]);
+    }
+
+    #[test]
+    fn range_chars_highlights_part_of_a_token() {
+        let r = rules(
+            r#"
+            [rules.sep]
+            name = "sep"
+            description = "d"
+            range = [0, 0]
+            range_chars = [1, 1]
+            pattern = [["Literal", "/_/"]]
+            "#,
+        );
+        let source = "fn f() {\n    let n = 1_000;\n}";
+        let e = lint_str(source, "a.rs", &r).unwrap();
+
+        let span = ty::span(&e[0].position);
+        assert_eq!(span.len(), 1);
+        assert_eq!(&source[span.offset()..span.offset() + span.len()], "_");
+        assert_eq!((e[0].position.start_line, e[0].position.start_col), (2, 14));
+    }
+
+    #[test]
+    fn orders_overlapping_lints_by_priority() {
+        let r = rules(
+            r#"
+            [rules.low]
+            name = "low"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "b"]]
+
+            [rules.high]
+            name = "high"
+            description = "d"
+            priority = 5
+            range = [0, 1]
+            pattern = [["Ident", "b"], ["Punct", "."]]
+
+            [rules.early]
+            name = "early"
+            description = "d"
+            priority = -5
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+            "#,
+        );
+        let names = lint_str("fn f() { b.c(); a; }", "a.rs", &r)
+            .unwrap()
+            .iter()
+            .map(|e| e.rule.name.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, ["high", "low", "early"]);
+    }
+
+    #[test]
+    fn sources_are_matched_on_their_own() {
+        let r = rules(UNWRAP);
+        let ends = ["fn a() {} x.", "unwrap();"];
+
+        assert!(ends
+            .iter()
+            .all(|s| lint_str(s, "a.rs", &r).unwrap().is_empty()));
+        assert_eq!(lint_str(&ends.concat(), "a.rs", &r).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tokenizes_snippets_for_match_rule() {
+        let tokens = tokenize("x.unwrap(1)").unwrap();
+        let named = tokens
+            .iter()
+            .map(|t| (t.kind(), t.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            named,
+            [
+                ("Ident", "x"),
+                ("Punct", "."),
+                ("Ident", "unwrap"),
+                ("Delim", "("),
+                ("Literal", "1"),
+                ("Delim", ")"),
+            ]
+        );
+
+        let rule = rules(UNWRAP).rules["u"].clone();
+        assert!(match_rule(rule.clone(), &tokens).is_empty());
+        assert_eq!(match_rule(rule, &tokenize("x.unwrap()").unwrap()).len(), 1);
+
+        // Located at the mismatched closing delimiter
+        let e = tokenize("fn f() {\n    (]\n}").unwrap_err();
+        assert_eq!((e.line, e.column), (2, 6));
+    }
+
+    #[test]
+    fn applies_to_limits_rules_to_matching_files() {
+        let r = rules(
+            r#"
+            [rules.env]
+            name = "env"
+            description = "d"
+            applies_to = ["build.rs"]
+            range = [0, 0]
+            pattern = [["Ident", "env"]]
+            "#,
+        );
+        let count = |file: &str| lint_str("fn f() { env; }", file, &r).unwrap().len();
+
+        assert_eq!(count("build.rs"), 1);
+        assert_eq!(count("crates/a/build.rs"), 1);
+        assert_eq!(count("src/main.rs"), 0);
+    }
+
+    #[test]
+    fn failed_starts_dont_skip_later_matches() {
+        let r = rules(
+            r#"
+            [rules.ab]
+            name = "ab"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "a"], ["Ident", "b"]]
+            "#,
+        );
+        let matched = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(LintError::matched)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched("a a b a b"), ["a b", "a b"]);
+        assert!(matched("a a a").is_empty());
+
+        let window = r.rules["ab"]
+            .test(&tokenize("b a a b").unwrap())
+            .unwrap_err();
+        assert_eq!(
+            window.iter().map(Named::value).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn custom_rules_report_alongside_patterns() {
+        struct Fns;
+        impl CustomRule for Fns {
+            fn check(&self, tokens: &[Named], source: &str) -> Vec<LintError> {
+                tokens
+                    .iter()
+                    .filter(|t| t.value() == "fn")
+                    .map(|t| LintError::new(Rule::new("fns", "d"), vec![t.clone()], source, ""))
+                    .collect()
+            }
+        }
+
+        let source = "fn f() {\n    x.unwrap();\n}\nfn g() {}";
+        let errors = test_with_custom(
+            rules(UNWRAP),
+            &[Box::new(Fns)],
+            tokenize(source).unwrap(),
+            source.to_string(),
+            "a.rs".to_string(),
+        );
+        let found = errors
+            .iter()
+            .map(|e| (e.rule.name.as_str(), e.position.start_line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(found, [("fns", 1), ("u", 2), ("fns", 4)]);
+        assert!(errors.iter().all(|e| e.source.name() == "a.rs"));
+    }
+
+    #[test]
+    fn operators_are_joined() {
+        let r = rules(
+            r#"
+            [options]
+            operators = true
+
+            [rules.range]
+            name = "range"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Op", "..="]]
+
+            [rules.assign]
+            name = "assign"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Punct", "="]]
+            "#,
+        );
+        let matched = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| (e.rule.name.clone(), e.matched()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            matched("fn f() { let r = 1..=9; }"),
+            [
+                ("assign".into(), "=".into()),
+                ("range".into(), "..=".into())
+            ]
+        );
+        assert_eq!(
+            matched("fn f() { let r = 1..9 == x; }"),
+            [("assign".to_string(), "=".to_string())]
+        );
+    }
+
+    #[test]
+    fn lints_rust_blocks_of_markdown() {
+        let markdown = "# Title\n\n```rust\nfn f() {\n    x.unwrap();\n}\n```\n\n```text\nx.unwrap()\n```\n\n```rust,ignore\ny.unwrap()\n";
+        let errors = lint_markdown(markdown, "a.md", &rules(UNWRAP)).unwrap();
+        let found = errors
+            .iter()
+            .map(|e| (e.position.start_line, e.position.start_col))
+            .collect::<Vec<_>>();
+
+        assert_eq!(found, [(5, 6), (14, 2)]);
+        assert!(errors.iter().all(|e| e.source.inner().as_str() == markdown));
+    }
+
+    #[test]
+    fn blank_lines_are_tokens() {
+        let r = rules(
+            r#"
+            [options]
+            blank_lines = true
+
+            [rules.two]
+            name = "two"
+            description = "d"
+            range = [1, 1]
+            pattern = [{ kind = "BlankLine" }, { kind = "BlankLine" }]
+            "#,
+        );
+        let lines = |source: &str| {
+            lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.position.start_line)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines("fn a() {}\n\nfn b() {}\n\n  \nfn c() {}\n"), [5]);
+        assert!(lines("fn a() {\n    \"\n\n\n\";\n}\n").is_empty());
+        assert_eq!(lines("fn a() {}\n\n\nfn b() {}\n"), [3]);
+    }
+
+    #[test]
+    fn markdown_outside_blocks_isnt_blank_lines() {
+        let r = rules(
+            r#"
+            [options]
+            blank_lines = true
+
+            [rules.blank]
+            name = "blank"
+            description = "d"
+            range = [0, 0]
+            pattern = [{ kind = "BlankLine" }]
+            "#,
+        );
+        let markdown = "# Title\n\n\n```rust\nfn a() {}\n\nfn b() {}\n```\n\n";
+        let lines = lint_markdown(markdown, "a.md", &r)
+            .unwrap()
+            .iter()
+            .map(|e| e.position.start_line)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines, [6]);
+    }
 }
@@ -1,16 +1,81 @@
-use clap::Parser;
+use baseline::Baseline;
+use clap::{Parser, Subcommand, ValueEnum};
 use itertools::Itertools;
-use miette::{bail, miette, Report};
+use miette::{bail, miette, Report, Severity};
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecursiveMode, Watcher,
+};
 use owo_colors::OwoColorize;
-use proc_macro2::TokenTree;
-use std::{fs, io::Error, process::Command, str::FromStr, time::Instant};
-use ty::{LintError, Named};
+use profile::Profile;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use ty::{LintError, LintRunError};
 
 use crate::ty::Rules;
 use splint::*;
 
 const RULES_FILES: [&str; 4] = ["splint.json", ".splint.json", "splint.toml", ".splint.toml"];
 
+/// Exit code for runs with failing lints
+const EXIT_FAILS: i32 = 1;
+
+/// Exit code for rules, IO and parse errors
+const EXIT_ERROR: i32 = 2;
+
+/// How long watch mode waits for saves to settle before re-linting
+const WATCH_POLL: Duration = Duration::from_millis(250);
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Rendered miette reports
+    Human,
+    /// GitHub Actions workflow command annotations
+    Github,
+    /// One JSON object per lint
+    Jsonl,
+    /// The number of fails and warnings
+    Count,
+    /// A Checkstyle XML report
+    Checkstyle,
+    /// A JUnit XML report, with a test suite per file and a test case per lint
+    Junit,
+    /// One JSON object with the number of files, fails, warnings and lints per rule
+    SummaryJson,
+}
+
+#[derive(ValueEnum, Debug, Clone, PartialEq)]
+enum InputFormat {
+    /// Rust source files
+    Rust,
+    /// The ```rust code blocks of markdown files
+    Markdown,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Commands {
+    /// Check the rules parse and can match, without linting anything
+    CheckConfig,
+    /// Print a JSON Schema for rules files, e.g. for editor autocompletion
+    Schema,
+    /// Print everything about a rule, e.g. one named in a CI log
+    Explain {
+        /// The rule's name
+        rule: String,
+    },
+}
+
 #[derive(Parser, Debug, Clone)]
 #[command(
     version = "1.0.0",
@@ -18,20 +83,187 @@ const RULES_FILES: [&str; 4] = ["splint.json", ".splint.json", "splint.toml", ".
     ignore_errors = true
 )]
 struct Args {
-    #[arg(short = 'r', help = "The rules to lint against (json|toml)")]
-    rules: Option<String>,
-    #[arg(name = "FILES", help = "The files to lint")]
+    #[command(subcommand)]
+    command: Option<Commands>,
+    #[arg(
+        short = 'r',
+        global = true,
+        value_delimiter = ',',
+        help = "The rules to lint against (json|toml), later files override earlier ones"
+    )]
+    rules: Vec<String>,
+    #[arg(name = "FILES", help = "The files to lint, or - for stdin")]
     files: Vec<String>,
     #[arg(short = 'q', default_value = "false", help = "Quiet mode")]
     quiet: bool,
     #[arg(short = 'a', default_value = "false", help = "RustAnalyzer mode")]
     analyze: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Also run `cargo check` in RustAnalyzer mode"
+    )]
+    with_cargo_check: bool,
+    #[arg(long = "tag", help = "Only run rules with the given tag")]
+    tags: Vec<String>,
+    #[arg(long, value_enum, default_value_t = Format::Human, help = "The output format")]
+    format: Format,
+    #[arg(long, value_enum, default_value_t = InputFormat::Rust, help = "The format of the files linted")]
+    input_format: InputFormat,
+    #[arg(long, help = "Stop reporting a rule after this many matches per file")]
+    max_per_rule: Option<usize>,
+    #[arg(long, help = "The file name to report for input read from stdin")]
+    stdin_filename: Option<String>,
+    #[arg(long, default_value = "false", help = "Re-lint files as they change")]
+    watch: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Exit successfully even if there are fails"
+    )]
+    exit_zero: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Look for a rules file in parent directories, up to the repository root"
+    )]
+    config_search: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Apply rule replacements, printing the result for stdin"
+    )]
+    fix: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "fix",
+        help = "Print the changes --fix would make as a diff, without writing them"
+    )]
+    dry_run: bool,
+    #[arg(long, default_value = "false", help = "Disable colored output")]
+    no_color: bool,
+    #[arg(long, help = "Skip files matching the given glob, after expansion")]
+    exclude_glob: Vec<String>,
+    #[arg(
+        long,
+        help = "The number of files to lint at once, defaults to the number of CPUs"
+    )]
+    threads: Option<usize>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Only report the first match of each rule in each file"
+    )]
+    first_only: bool,
+    #[arg(long, help = "Suppress lints recorded in this baseline file")]
+    baseline: Option<String>,
+    #[arg(
+        long,
+        default_value = "false",
+        requires = "baseline",
+        help = "Record the current lints in the --baseline file"
+    )]
+    write_baseline: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Print how many times each rule matched, most frequent first"
+    )]
+    summary: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Print how long each rule took to match, slowest first"
+    )]
+    profile: bool,
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Fail when there are more than this many lints in total, fails or warnings"
+    )]
+    error_on: Option<usize>,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "List the rules that didn't match anything"
+    )]
+    coverage: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Report file names relative to the current directory"
+    )]
+    relative_paths: bool,
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Print each file to stderr as it starts being linted"
+    )]
+    progress: bool,
+}
+
+impl Args {
+    /// Whether --fix prints fixed stdin or a diff, which other output shouldn't be mixed into
+    fn fixes_to_stdout(&self) -> bool {
+        self.fix && (self.dry_run || self.files.iter().any(|f| f == "-"))
+    }
 }
 
 pub fn main() {
     let args: Args = Args::parse();
+    if !use_color(&args) {
+        miette::set_hook(Box::new(|_| {
+            Box::new(miette::MietteHandlerOpts::new().color(false).build())
+        }))
+        .ok();
+    }
+
+    if let Some(Commands::Schema) = args.command {
+        let schema = schemars::schema_for!(Rules);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        return;
+    }
+
+    if let Some(Commands::CheckConfig) = args.command {
+        match load_rules(&args) {
+            Ok(r) => {
+                if !args.quiet {
+                    println!("{} rules OK", r.rules.len());
+                }
+            }
+            Err(e) => {
+                eprintln!("{e:?}");
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        return;
+    }
+
+    if let Some(Commands::Explain { rule }) = &args.command {
+        match load_rules(&args).and_then(|r| explain(&r, rule)) {
+            Ok(text) => println!("{text}"),
+            Err(e) => {
+                eprintln!("{e:?}");
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        return;
+    }
+
+    if args.watch {
+        if let Err(e) = watch(args) {
+            eprintln!("{e:?}");
+            std::process::exit(EXIT_ERROR);
+        }
+
+        return;
+    }
+
     match cli(args.clone()) {
-        Ok((violations, file_count, ms)) => {
+        Ok((violations, errors, file_count, ms)) => {
             if args.analyze {
                 violations
                     .into_iter()
@@ -40,130 +272,718 @@ pub fn main() {
                         println!("{}", f);
                     });
 
-                Command::new(env!("CARGO"))
-                    .arg("check")
-                    .arg("--quiet")
-                    .arg("--workspace")
-                    .arg("--message-format=json")
-                    .arg("--all-targets")
-                    .status()
-                    .unwrap();
+                if args.with_cargo_check {
+                    Command::new(env!("CARGO"))
+                        .arg("check")
+                        .arg("--quiet")
+                        .arg("--workspace")
+                        .arg("--message-format=json")
+                        .arg("--all-targets")
+                        .status()
+                        .unwrap();
+                }
 
+                report_errors(&errors);
                 std::process::exit(0);
             } else {
-                let fails = violations.iter().filter(|a| a.fails);
-                if !args.quiet {
-                    violations
-                        .clone()
-                        .into_iter()
-                        .map(Report::new)
-                        .for_each(|r| {
-                            eprintln!("{r:?}");
-                        });
-
-                    println!(
-                        "{}, {}",
-                        format!("{} fails", fails.clone().count()).red(),
-                        format!("{} warnings", violations.len()).yellow()
-                    );
-                    println!("Finished linting {} files in {}ms", file_count, ms);
+                let (fails, warnings): (Vec<_>, Vec<_>) = violations.iter().partition(|a| a.fails);
+                match args.format {
+                    Format::Github => violations
+                        .iter()
+                        .for_each(|e| println!("{}", e.github_annotation())),
+                    Format::Jsonl => violations
+                        .iter()
+                        .map(|e| serde_json::to_string(&e.to_serializable()).unwrap())
+                        .for_each(|f| println!("{}", f)),
+                    Format::Count => println!("{} {}", fails.len(), warnings.len()),
+                    Format::Checkstyle => println!("{}", checkstyle::checkstyle(&violations)),
+                    Format::Junit => println!("{}", junit::junit(&violations)),
+                    Format::SummaryJson => {
+                        let summary = serializable::RunSummary::new(&violations, file_count);
+                        println!("{}", serde_json::to_string(&summary).unwrap());
+                    }
+                    Format::Human => {
+                        if !args.quiet {
+                            report(&violations);
+                        }
+                        let mut lines = match args.summary {
+                            true => tally(&violations, use_color(&args)),
+                            false => vec![],
+                        };
+                        if !args.quiet {
+                            lines.push(summary(
+                                fails.len(),
+                                warnings.len(),
+                                file_count,
+                                ms,
+                                use_color(&args),
+                            ));
+                        }
+                        for line in lines {
+                            match args.fixes_to_stdout() {
+                                true => eprintln!("{line}"),
+                                false => println!("{line}"),
+                            }
+                        }
+                    }
                 }
 
-                if fails.count() > 0 {
-                    std::process::exit(1);
+                report_errors(&errors);
+                if !errors.is_empty() {
+                    std::process::exit(EXIT_ERROR);
+                }
+
+                let over_budget = args.error_on.is_some_and(|n| violations.len() > n);
+                if (!fails.is_empty() || over_budget) && !args.exit_zero {
+                    std::process::exit(EXIT_FAILS);
                 }
             }
         }
         Err(e) => {
             eprintln!("{e:?}");
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         }
     }
 }
 
-fn cli(args: Args) -> miette::Result<(Vec<LintError>, usize, u128)> {
-    let rules_path = args.rules.unwrap_or_else(|| {
-        let path = std::env::current_dir().unwrap();
-        RULES_FILES
+fn report(violations: &[LintError]) {
+    violations.iter().cloned().map(Report::new).for_each(|r| {
+        eprintln!("{r:?}");
+    });
+}
+
+/// Prints the files that couldn't be linted, after the lints of those that could
+fn report_errors(errors: &[LintRunError]) {
+    errors
+        .iter()
+        .for_each(|e| eprintln!("{:?}", miette!("Error linting files: {e}")));
+}
+
+fn summary(fails: usize, warnings: usize, file_count: usize, ms: u128, color: bool) -> String {
+    let (fails, warnings) = (format!("{} fails", fails), format!("{} warnings", warnings));
+    let counts = match color {
+        true => format!("{}, {}", fails.red(), warnings.yellow()),
+        false => format!("{}, {}", fails, warnings),
+    };
+    format!(
+        "{counts}\nFinished linting {} files in {}ms",
+        file_count, ms
+    )
+}
+
+/// The number of lints per rule, most frequent first, colored by whether the rule fails
+fn tally(violations: &[LintError], color: bool) -> Vec<String> {
+    let counts = violations
+        .iter()
+        .counts_by(|e| (e.rule.name.as_str(), e.fails));
+    counts
+        .into_iter()
+        .sorted_by(|(a, n), (b, m)| m.cmp(n).then(a.cmp(b)))
+        .map(|((name, fails), n)| {
+            let line = format!(
+                "{name}: {n} {}",
+                if n == 1 { "occurrence" } else { "occurrences" }
+            );
+            match (color, fails) {
+                (true, true) => line.red().to_string(),
+                (true, false) => line.yellow().to_string(),
+                (false, _) => line,
+            }
+        })
+        .collect()
+}
+
+/// Whether to color output, honoring --no-color, `NO_COLOR` and output that isn't a terminal
+fn use_color(args: &Args) -> bool {
+    !args.no_color
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && io::stdout().is_terminal()
+}
+
+/// Lints the files given by the arguments, returning the lints of the files that could be linted
+/// and errors for those that couldn't
+fn cli(args: Args) -> miette::Result<(Vec<LintError>, Vec<LintRunError>, usize, u128)> {
+    let r = load_rules(&args)?;
+    let files = expand_files(&args)?;
+    if files.is_empty() {
+        bail!(miette!("No files provided."))
+    }
+
+    let baseline = match args.write_baseline {
+        true => None,
+        false => args.baseline.as_deref().map(read_baseline).transpose()?,
+    };
+
+    let s: Instant = Instant::now();
+    let (results, profile) = lint_files(&args, &r, &files);
+    if args.profile {
+        print_profile(&r, profile);
+    }
+
+    // Fixes are applied in file order, so diffs and fixed stdin print in a stable order
+    if args.fix {
+        files
             .iter()
-            .map(|f| path.join(f))
-            .find(|f| f.exists())
+            .zip(&results)
+            .filter_map(|(f, v)| Some((f, v.as_ref().ok()?)))
+            .try_for_each(|(f, v)| fix_file(&args, f, v))
+            .map_err(|e| miette!("Error fixing files: {e}"))?;
+    }
+
+    let (linted, errors): (Vec<_>, Vec<_>) = results.into_iter().partition_result();
+    let mut violations = linted
+        .into_iter()
+        .flat_map(|v| first_only(&args, v))
+        .collect_vec();
+
+    if args.coverage {
+        print_coverage(&r, &violations);
+    }
+
+    if let Some(baseline) = baseline {
+        violations = baseline.suppress(violations);
+    }
+
+    if let (true, Some(path)) = (args.write_baseline, &args.baseline) {
+        write_baseline(path, &violations)?;
+        if !args.quiet {
+            eprintln!("Recorded {} lints in {path}", violations.len());
+        }
+        violations.clear();
+    }
+
+    Ok((violations, errors, files.len(), s.elapsed().as_millis()))
+}
+
+fn read_baseline(path: &str) -> miette::Result<Baseline> {
+    let content =
+        fs::read_to_string(path).map_err(|e| miette!("Couldn't read baseline: {:?}", e))?;
+    serde_json::from_str(&content).map_err(|e| miette!("Couldn't parse baseline in {path}: {e}"))
+}
+
+fn write_baseline(path: &str, violations: &[LintError]) -> miette::Result<()> {
+    let json = serde_json::to_string_pretty(&Baseline::new(violations)).unwrap();
+    fs::write(path, json + "\n").map_err(|e| miette!("Couldn't write baseline: {:?}", e))
+}
+
+/// Describes a rule, looked up by name among the rules and override rules
+fn explain(r: &Rules, name: &str) -> miette::Result<String> {
+    let Some(rule) = r
+        .rules
+        .iter()
+        .chain(r.overrides.iter().flat_map(|o| o.rules.iter()))
+        .find(|(k, rule)| *k == name || rule.name == name)
+        .map(|(_, rule)| rule)
+    else {
+        bail!("No rule named '{name}'");
+    };
+
+    let severity = match rule.fails {
+        true => "error",
+        false => "warning",
+    };
+    let mut out = format!("{} ({severity})\n{}\n", rule.name, rule.description);
+    for (label, v) in [
+        ("help", &rule.help),
+        ("note", &rule.note),
+        ("link", &rule.link),
+        ("replace", &rule.replace),
+    ] {
+        if let Some(v) = v {
+            out.push_str(&format!("\n{label}: {v}"));
+        }
+    }
+
+    if !rule.tags.is_empty() {
+        out.push_str(&format!("\ntags: {}", rule.tags.join(", ")));
+    }
+
+    let pattern = match rule.repeated_tokens {
+        Some(n) => format!("any {n} tokens repeating earlier ones"),
+        None => rule.pattern.iter().map(|n| format!("{n:?}")).join(" "),
+    };
+    out.push_str(&format!(
+        "\npattern: {pattern}\nrange: [{}, {}]",
+        rule.range.start(),
+        rule.range.end()
+    ));
+
+    Ok(out)
+}
+
+/// Prints the rules, including override rules, without any lints
+fn print_coverage(r: &Rules, violations: &[LintError]) {
+    let unmatched = r
+        .rules
+        .values()
+        .chain(r.overrides.iter().flat_map(|o| o.rules.values()))
+        .map(|rule| rule.name.as_str())
+        .unique()
+        .filter(|name| !violations.iter().any(|e| e.rule.name == *name))
+        .collect_vec();
+
+    match unmatched.is_empty() {
+        true => eprintln!("Every rule matched"),
+        false => {
+            eprintln!("Rules that never matched:");
+            unmatched.iter().for_each(|name| eprintln!("  {name}"));
+        }
+    }
+}
+
+/// Prints each rule's matching time and match count, including rules that never ran
+fn print_profile(r: &Rules, mut profile: Profile) {
+    r.rules
+        .values()
+        .chain(r.overrides.iter().flat_map(|o| o.rules.values()))
+        .for_each(|rule| profile.record(&rule.name, Duration::ZERO, 0));
+
+    for (name, t) in profile.sorted() {
+        eprintln!(
+            "{name}: {:.3}ms, {} matches",
+            t.elapsed.as_secs_f64() * 1000.0,
+            t.matches
+        );
+    }
+}
+
+/// Lints files across worker threads, returning results in file order
+/// along with the time each rule took
+fn lint_files(
+    args: &Args,
+    r: &Rules,
+    files: &[String],
+) -> (Vec<Result<Vec<LintError>, LintRunError>>, Profile) {
+    let threads = args
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, files.len().max(1));
+    let next = AtomicUsize::new(0);
+
+    let workers = thread::scope(|s| {
+        (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut out = Vec::new();
+                    let mut profile = Profile::default();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(f) = files.get(i) else {
+                            break;
+                        };
+
+                        if args.progress {
+                            eprintln!("Linting {f} ({}/{})", i + 1, files.len());
+                        }
+
+                        out.push((i, lint_file(args, r, f, &mut profile)));
+                    }
+                    (out, profile)
+                })
+            })
+            .collect_vec()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect_vec()
+    });
+
+    let mut profile = Profile::default();
+    let mut results = workers
+        .into_iter()
+        .flat_map(|(out, p)| {
+            profile.merge(p);
+            out
+        })
+        .collect_vec();
+    results.sort_by_key(|(i, _)| *i);
+    (results.into_iter().map(|(_, v)| v).collect(), profile)
+}
+
+/// With --first-only, keeps just the earliest lint of each rule in a file's lints
+fn first_only(args: &Args, violations: Vec<LintError>) -> Vec<LintError> {
+    match args.first_only {
+        true => violations
+            .into_iter()
+            .unique_by(|e| e.rule.name.clone())
+            .collect(),
+        false => violations,
+    }
+}
+
+/// Lints once, then re-lints files whenever they change
+fn watch(args: Args) -> miette::Result<()> {
+    let r = load_rules(&args)?;
+    let baseline = args.baseline.as_deref().map(read_baseline).transpose()?;
+    let mut results = HashMap::<String, Vec<LintError>>::new();
+    let mut modified = HashMap::<String, Option<SystemTime>>::new();
+
+    let (tx, events) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| miette!("Couldn't watch files: {e}"))?;
+    for (root, mode) in watch_roots(&args) {
+        watcher
+            .watch(&root, mode)
+            .map_err(|e| miette!("Couldn't watch {}: {e}", root.display()))?;
+    }
+
+    let mut rescan = true;
+    loop {
+        let changed = changed_files(&args, &mut modified, rescan)?;
+        if !changed.is_empty() {
+            let s: Instant = Instant::now();
+            results.retain(|f, _| modified.contains_key(f));
+            for f in changed {
+                let lints =
+                    lint_file(&args, &r, &f, &mut Profile::default()).map(|v| match &baseline {
+                        Some(b) => b.suppress(first_only(&args, v)),
+                        None => first_only(&args, v),
+                    });
+                match lints {
+                    Ok(v) => {
+                        report(&v);
+                        results.insert(f, v);
+                    }
+                    Err(e) => {
+                        eprintln!("{:?}", miette!("Error linting files: {e}"));
+                        results.remove(&f);
+                    }
+                }
+            }
+
+            let (fails, warnings): (Vec<_>, Vec<_>) =
+                results.values().flatten().partition(|a| a.fails);
+            println!(
+                "{}",
+                summary(
+                    fails.len(),
+                    warnings.len(),
+                    modified.len(),
+                    s.elapsed().as_millis(),
+                    use_color(&args),
+                )
+            );
+        }
+
+        // Wait for a change, letting rapid saves settle before re-linting
+        let mut event = events
+            .recv()
+            .map_err(|e| miette!("Stopped watching files: {e}"))?;
+        rescan = false;
+        loop {
+            rescan |= adds_or_removes(&event);
+            match events.recv_timeout(WATCH_POLL) {
+                Ok(next) => event = next,
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// The paths to watch for the file arguments: directories and glob bases recursively,
+/// and the directories holding plain files, as editors often replace files when saving
+fn watch_roots(args: &Args) -> Vec<(PathBuf, RecursiveMode)> {
+    args.files
+        .iter()
+        .filter(|loc| *loc != "-")
+        .map(|loc| {
+            let path = Path::new(loc);
+            if path.is_dir() {
+                return (path.to_path_buf(), RecursiveMode::Recursive);
+            }
+
+            if loc.contains('*') {
+                let base = path
+                    .components()
+                    .take_while(|c| !c.as_os_str().to_string_lossy().contains('*'))
+                    .collect::<PathBuf>();
+                return (dir_or_cwd(base), RecursiveMode::Recursive);
+            }
+
+            let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (dir_or_cwd(parent), RecursiveMode::NonRecursive)
+        })
+        .unique()
+        .collect()
+}
+
+fn dir_or_cwd(dir: PathBuf) -> PathBuf {
+    match dir.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => dir,
+    }
+}
+
+/// Whether an event may have added or removed files, so the file arguments need expanding again
+fn adds_or_removes(event: &notify::Result<Event>) -> bool {
+    event.as_ref().map_or(true, |e| {
+        e.kind.is_create()
+            || e.kind.is_remove()
+            || matches!(e.kind, EventKind::Modify(ModifyKind::Name(_)))
+    })
+}
+
+/// Finds files that were added or modified since last checked, forgetting removed ones.
+/// Only expands the file arguments again when asked to, otherwise checking the known files.
+fn changed_files(
+    args: &Args,
+    modified: &mut HashMap<String, Option<SystemTime>>,
+    rescan: bool,
+) -> miette::Result<Vec<String>> {
+    let files = match rescan {
+        true => expand_files(args)?
+            .into_iter()
+            .filter(|f| f != "-")
+            .unique()
+            .collect_vec(),
+        false => modified.keys().cloned().sorted().collect_vec(),
+    };
+    modified.retain(|f, _| files.contains(f));
+
+    Ok(files
+        .into_iter()
+        .filter(|f| {
+            let time = fs::metadata(f).and_then(|m| m.modified()).ok();
+            modified.insert(f.clone(), time) != Some(time)
+        })
+        .collect())
+}
+
+fn load_rules(args: &Args) -> miette::Result<Rules> {
+    let rules_paths = if args.rules.is_empty() {
+        vec![find_rules_file(args.config_search)
             .unwrap_or_else(|| {
                 if !args.quiet {
                     eprintln!("{:?}", miette!("Couldn't find rules file in current directory. You can specify one with -r"));
                 }
-                std::process::exit(1);
+                std::process::exit(EXIT_ERROR);
             })
             .to_str()
             .unwrap()
-            .to_string()
-    });
+            .to_string()]
+    } else {
+        args.rules.clone()
+    };
+
+    let mut r = Rules::default();
+    for rules_path in rules_paths {
+        r.merge(read_rules(&rules_path)?);
+    }
+
+    let unknown = r.unknown_overridden();
+    if !unknown.is_empty() {
+        return Err(miette!("Couldn't apply overrides:\n{}", unknown.join("\n")));
+    }
+    r.retain_tags(&args.tags);
+    if !args.quiet {
+        r.shadowed()
+            .into_iter()
+            .for_each(|w| eprintln!("{:?}", miette!(severity = Severity::Warning, "{w}")));
+    }
 
-    let r: Rules = {
-        let content = fs::read_to_string(rules_path.clone())
-            .map_err(|e| miette!("Couldn't read rules: {:?}", e))?;
-        if rules_path.ends_with(".toml") {
-            toml::from_str(&content).map_err(|e| miette!("Couldn't parse rules: {:?}", e))?
-        } else {
-            serde_json::from_str(&content).map_err(|e| miette!("Couldn't parse rules: {:?}", e))?
+    if args.max_per_rule.is_some() {
+        r.options.max_per_rule = args.max_per_rule;
+    }
+
+    Ok(r)
+}
+
+/// Finds the default rules file in the cwd, or its nearest ancestor when searching
+fn find_rules_file(search: bool) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let mut dirs = cwd.ancestors();
+
+    loop {
+        let dir = dirs.next()?;
+        let found = RULES_FILES.iter().map(|f| dir.join(f)).find(|f| f.exists());
+        if found.is_some() || !search || dir.join(".git").exists() {
+            return found;
         }
+    }
+}
+
+/// Expands globs and directories in the file arguments into a list of files, minus exclusions.
+/// Arguments without glob characters, or that name an existing file, are taken as they are.
+fn expand_files(args: &Args) -> miette::Result<Vec<String>> {
+    let exclude = args
+        .exclude_glob
+        .iter()
+        .map(|p| ty::glob(p).map_err(|e| miette!("Invalid --exclude-glob '{p}': {e}")))
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    Ok(args
+        .files
+        .iter()
+        .flat_map(|loc| {
+            let pattern = if Path::new(loc).is_dir() {
+                let ext = match args.input_format {
+                    InputFormat::Rust => "rs",
+                    InputFormat::Markdown => "md",
+                };
+                format!("{}/**/*.{ext}", loc.trim_end_matches('/'))
+            } else if loc.contains(['*', '?', '[', '{']) && !Path::new(loc).is_file() {
+                loc.to_string()
+            } else {
+                return vec![loc.to_string()];
+            };
+
+            walk_glob(&pattern).unwrap_or_else(|_| vec![loc.to_string()])
+        })
+        .filter(|f| !exclude.iter().any(|p| p.is_match(f)))
+        .collect())
+}
+
+/// Finds the files matching a glob, walking from the directories before its first glob character.
+/// Globs are read as [`ty::glob`] reads them.
+fn walk_glob(pattern: &str) -> Result<Vec<String>, globset::Error> {
+    let matcher = ty::glob(pattern)?;
+    let base = pattern
+        .split('/')
+        .take_while(|c| !c.contains(['*', '?', '[', '{']))
+        .collect_vec()
+        .join("/");
+    let root = match base.is_empty() {
+        true => ".",
+        false => &base,
     };
 
-    let files = args.files.iter().flat_map(|loc| {
-        if !loc.contains('*') {
-            vec![loc.to_string()]
-        } else {
-            glob::glob(&loc)
-                .unwrap()
-                .filter_map(Result::ok)
-                .map(|p| p.into_os_string().to_str().unwrap().to_string())
-                .collect::<Vec<_>>()
+    Ok(walkdir::WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let path = e.path().to_str()?;
+            let path = match base.is_empty() {
+                true => path.strip_prefix("./").unwrap_or(path),
+                false => path,
+            };
+            matcher.is_match(path).then(|| path.to_string())
+        })
+        .collect())
+}
+
+fn lint_file(
+    args: &Args,
+    r: &Rules,
+    f: &str,
+    profile: &mut Profile,
+) -> Result<Vec<LintError>, LintRunError> {
+    let (input, name, rules) = match f {
+        "-" => {
+            let name = args.stdin_filename.as_deref().unwrap_or("<stdin>");
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|error| LintRunError::Io {
+                    file: name.to_string(),
+                    error,
+                })?;
+            (input, name.to_string(), r.for_file(name))
         }
-    });
+        _ => {
+            let input = fs::read_to_string(f).map_err(|error| LintRunError::Io {
+                file: f.to_string(),
+                error,
+            })?;
+            let name = match args.relative_paths {
+                true => relative_path(f),
+                false => f.to_string(),
+            };
+            (input, name, r.for_file(f))
+        }
+    };
 
-    if files.clone().count() == 0 {
-        bail!(miette!("No files provided."))
+    match args.input_format {
+        InputFormat::Rust => lint_str_profiled(&input, &name, &rules, profile),
+        InputFormat::Markdown => lint_markdown_profiled(&input, &name, &rules, profile),
     }
+}
 
-    let s: Instant = Instant::now();
-    let violations = files
-        .clone()
-        .map(|f| lint(f, r.clone()))
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| miette!("Error linting files: {:?}", e))?
-        .into_iter()
-        .flatten()
-        .collect_vec();
+/// A file's path relative to the current directory, or as given if it's elsewhere
+fn relative_path(f: &str) -> String {
+    let (Ok(cwd), Ok(path)) = (
+        std::env::current_dir().and_then(|d| d.canonicalize()),
+        Path::new(f).canonicalize(),
+    ) else {
+        return f.to_string();
+    };
 
-    Ok((violations, files.count(), s.elapsed().as_millis()))
+    match path.strip_prefix(cwd) {
+        Ok(p) => p.to_string_lossy().to_string(),
+        Err(_) => f.to_string(),
+    }
 }
 
-pub fn lint(loc: String, rules: Rules) -> Result<Vec<LintError>, Error> {
-    let input = fs::read_to_string(loc.clone())?;
-    let token_tree = proc_macro2::TokenStream::from_str(&input).unwrap();
-    let named = token_tree
-        .into_iter()
-        .map(|tt| parse(tt))
-        .flatten()
-        .collect::<Vec<Named>>();
+/// Applies, or with --dry-run prints, the replacements suggested for a file
+fn fix_file(args: &Args, f: &str, violations: &[LintError]) -> Result<(), LintRunError> {
+    let Some(source) = violations.first().map(|e| e.source.inner().clone()) else {
+        return Ok(());
+    };
+
+    let edits = fix::edits(violations);
+    if edits.is_empty() {
+        return Ok(());
+    }
 
-    Ok(test(rules, named, input.to_string(), loc))
+    if args.dry_run {
+        print!(
+            "{}",
+            fix::diff(violations[0].source.name(), &source, &edits)
+        );
+        return Ok(());
+    }
+
+    if f == "-" {
+        print!("{}", fix::apply(&source, &edits));
+        return Ok(());
+    }
+
+    let original = fs::read_to_string(f).map_err(|error| LintRunError::Io {
+        file: f.to_string(),
+        error,
+    })?;
+    let fixed = fix::apply(&original, &fix::to_original(&original, &edits));
+    fs::write(f, fixed).map_err(|error| LintRunError::Io {
+        file: f.to_string(),
+        error,
+    })
 }
 
-fn parse(tt: TokenTree) -> Vec<Named> {
-    match tt {
-        TokenTree::Group(g) => {
-            let delim = Named::delim_pair(g.delimiter(), g.span_open(), g.span_close());
-            let mut body = vec![delim[0].clone()];
+fn read_rules(rules_path: &str) -> miette::Result<Rules> {
+    let content = match remote::is_url(rules_path) {
+        true => {
+            let fetched = remote::read(rules_path)
+                .map_err(|e| miette!("Couldn't fetch rules from {rules_path}: {e}"))?;
+            if let Some(w) = fetched.warning {
+                eprintln!("{:?}", miette!(severity = Severity::Warning, "{w}"));
+            }
+            fetched.content
+        }
+        false => {
+            fs::read_to_string(rules_path).map_err(|e| miette!("Couldn't read rules: {:?}", e))?
+        }
+    };
+    let toml = rules_path.ends_with(".toml");
+    let parsed = match toml {
+        true => toml::from_str(&content).map_err(|e| e.to_string()),
+        false => serde_json::from_str(&content).map_err(|e| e.to_string()),
+    };
+
+    parsed.map_err(|e| {
+        let value = match toml {
+            true => toml::from_str::<toml::Value>(&content)
+                .ok()
+                .and_then(|v| serde_json::to_value(v).ok()),
+            false => serde_json::from_str(&content).ok(),
+        };
 
-            g.stream()
-                .into_iter()
-                .map(|tt| parse(tt))
-                .for_each(|v| body.extend(v));
+        if let Some(shape) = value.as_ref().and_then(Rules::find_invalid_shape) {
+            return miette!("{rules_path} isn't a rules file: {shape}");
+        }
 
-            body.push(delim[1].clone());
-            body
+        match value.as_ref().and_then(Rules::find_invalid_rule) {
+            Some(rule) => miette!("Couldn't parse rules in {rules_path}: {rule}"),
+            None => miette!("Couldn't parse rules in {rules_path}: {e}"),
         }
-        _ => vec![tt.into()],
-    }
+    })
 }
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, time::Duration};
+
+/// Time spent matching a rule, and the matches it found, before any truncation
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RuleTiming {
+    pub elapsed: Duration,
+    pub matches: usize,
+}
+
+/// Per-rule matching times, keyed by rule name
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    pub rules: HashMap<String, RuleTiming>,
+}
+
+impl Profile {
+    pub fn record(&mut self, rule: &str, elapsed: Duration, matches: usize) {
+        let t = self.rules.entry(rule.to_string()).or_default();
+        t.elapsed += elapsed;
+        t.matches += matches;
+    }
+
+    /// Adds another profile's timings, e.g. from another thread, into this one
+    pub fn merge(&mut self, other: Profile) {
+        for (rule, t) in other.rules {
+            self.record(&rule, t.elapsed, t.matches);
+        }
+    }
+
+    /// The timings, slowest rule first
+    pub fn sorted(&self) -> Vec<(&str, RuleTiming)> {
+        let mut out = self
+            .rules
+            .iter()
+            .map(|(k, v)| (k.as_str(), *v))
+            .collect::<Vec<_>>();
+        out.sort_by(|(a, x), (b, y)| y.elapsed.cmp(&x.elapsed).then(a.cmp(b)));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_and_sorts_slowest_first() {
+        let mut a = Profile::default();
+        a.record("fast", Duration::from_millis(1), 2);
+        a.record("slow", Duration::from_millis(5), 1);
+        let mut b = Profile::default();
+        b.record("fast", Duration::from_millis(2), 1);
+        b.record("idle", Duration::ZERO, 0);
+        a.merge(b);
+
+        let sorted = a
+            .sorted()
+            .into_iter()
+            .map(|(name, t)| (name, t.elapsed.as_millis(), t.matches))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, [("slow", 5, 1), ("fast", 3, 3), ("idle", 0, 0)]);
+    }
+}
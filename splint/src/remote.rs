@@ -0,0 +1,199 @@
+use std::{fs, time::Duration};
+
+/// How long rules fetched from a URL are reused before being fetched again
+pub const TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Rules read from a URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fetched {
+    pub content: String,
+    /// Set when fetching failed and a stale cached copy was used instead
+    pub warning: Option<String>,
+}
+
+/// Whether a rules path is a URL rather than a file
+pub fn is_url(path: &str) -> bool {
+    ["http://", "https://", "file://"]
+        .iter()
+        .any(|s| path.starts_with(s))
+}
+
+/// Reads rules from a URL, reusing a copy cached within the [`TTL`],
+/// or, with a warning, a stale copy when fetching fails
+pub fn read(url: &str) -> Result<Fetched, String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return fs::read_to_string(path)
+            .map(|content| Fetched {
+                content,
+                warning: None,
+            })
+            .map_err(|e| e.to_string());
+    }
+
+    read_http(url)
+}
+
+#[cfg(not(feature = "remote"))]
+fn read_http(_url: &str) -> Result<Fetched, String> {
+    Err("reading rules from a URL needs splint built with the `remote` feature".to_string())
+}
+
+#[cfg(feature = "remote")]
+fn read_http(url: &str) -> Result<Fetched, String> {
+    let cache = cache_dir().map(|d| d.join(format!("{:016x}", crate::baseline::fnv1a(url))));
+    read_through(url, cache.as_deref(), fetch)
+}
+
+/// The current user's cache directory for fetched rules,
+/// under `$XDG_CACHE_HOME`, `~/.cache` or `%LOCALAPPDATA%`
+#[cfg(feature = "remote")]
+fn cache_dir() -> Option<std::path::PathBuf> {
+    use std::{env, path::PathBuf};
+
+    let absolute = |var: &str| {
+        env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+    };
+    let base = absolute("XDG_CACHE_HOME")
+        .or_else(|| absolute("HOME").map(|h| h.join(".cache")))
+        .or_else(|| absolute("LOCALAPPDATA"))?;
+
+    Some(base.join("splint").join("rules"))
+}
+
+/// Reads a URL through a cache file, if there's somewhere to keep one
+#[cfg(feature = "remote")]
+fn read_through(
+    url: &str,
+    cache: Option<&std::path::Path>,
+    fetch: impl FnOnce(&str) -> Result<String, String>,
+) -> Result<Fetched, String> {
+    let age = cache
+        .and_then(|c| fs::metadata(c).ok())
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.elapsed().ok());
+    if let (Some(cache), true) = (cache, age.is_some_and(|age| age < TTL)) {
+        if let Ok(content) = fs::read_to_string(cache) {
+            return Ok(Fetched {
+                content,
+                warning: None,
+            });
+        }
+    }
+
+    match fetch(url) {
+        Ok(content) => {
+            // The cache only saves a fetch, so failing to write it isn't worth failing over
+            if let Some(cache) = cache {
+                if let Some(dir) = cache.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+                let _ = fs::write(cache, &content);
+            }
+            Ok(Fetched {
+                content,
+                warning: None,
+            })
+        }
+        Err(e) => {
+            let stale = cache
+                .and_then(|c| fs::read_to_string(c).ok())
+                .ok_or(e.clone())?;
+            let minutes = age.map_or(0, |a| a.as_secs() / 60);
+            Ok(Fetched {
+                content: stale,
+                warning: Some(format!(
+                    "Couldn't fetch rules from {url} ({e}), using a copy cached {minutes} minutes ago"
+                )),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+fn fetch(url: &str) -> Result<String, String> {
+    let mut body = Vec::new();
+    let mut easy = curl::easy::Easy::new();
+    easy.url(url).map_err(|e| e.to_string())?;
+    easy.follow_location(true).map_err(|e| e.to_string())?;
+    easy.fail_on_error(true).map_err(|e| e.to_string())?;
+    {
+        let mut transfer = easy.transfer();
+        transfer
+            .write_function(|data| {
+                body.extend_from_slice(data);
+                Ok(data.len())
+            })
+            .map_err(|e| e.to_string())?;
+        transfer.perform().map_err(|e| e.to_string())?;
+    }
+
+    String::from_utf8(body).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("splint-remote-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_file_urls() {
+        let path = scratch("file").join("rules.json");
+        fs::write(&path, "{}").unwrap();
+        let url = format!("file://{}", path.display());
+
+        assert!(is_url(&url));
+        assert_eq!(read(&url).unwrap().content, "{}");
+        assert!(read("file:///does/not/exist.json").is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "remote"))]
+    fn http_needs_the_remote_feature() {
+        let e = read("https://example.com/rules.json").unwrap_err();
+        assert!(e.contains("`remote` feature"));
+    }
+
+    #[test]
+    #[cfg(feature = "remote")]
+    fn caches_fetched_rules() {
+        let cache = scratch("cache").join("rules");
+        let fetched = read_through("https://a", Some(&cache), |_| Ok("{}".to_string())).unwrap();
+        assert_eq!(fetched.content, "{}");
+        assert_eq!(fs::read_to_string(&cache).unwrap(), "{}");
+
+        let cached = read_through("https://a", Some(&cache), |_| {
+            panic!("fetched a fresh copy")
+        });
+        assert_eq!(cached.unwrap().content, "{}");
+    }
+
+    #[test]
+    #[cfg(feature = "remote")]
+    fn warns_when_using_a_stale_copy() {
+        let cache = scratch("stale").join("rules");
+        fs::write(&cache, "{}").unwrap();
+        let old = std::time::SystemTime::now() - TTL * 2;
+        fs::File::options()
+            .write(true)
+            .open(&cache)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let stale =
+            read_through("https://a", Some(&cache), |_| Err("offline".to_string())).unwrap();
+        assert_eq!(stale.content, "{}");
+        assert!(stale.warning.unwrap().contains("offline"));
+
+        let none = read_through("https://a", None, |_| Err("offline".to_string()));
+        assert_eq!(none, Err("offline".to_string()));
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use to_and_fro::ToAndFro;
+
+use crate::{compiler::CompilerSpan, ty::LintError};
+
+#[derive(ToAndFro, Clone)]
+#[serde]
+#[casing("kebab")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Totals for a whole run, for dashboards
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RunSummary {
+    /// Number of files linted
+    pub files: usize,
+    /// Number of failing lints
+    pub fails: usize,
+    /// Number of warnings
+    pub warnings: usize,
+    /// Number of lints per rule name
+    pub rules: BTreeMap<String, usize>,
+}
+
+impl RunSummary {
+    pub fn new(lints: &[LintError], files: usize) -> Self {
+        let mut rules = BTreeMap::new();
+        for e in lints {
+            *rules.entry(e.rule.name.clone()).or_default() += 1;
+        }
+
+        let fails = lints.iter().filter(|e| e.fails).count();
+        Self {
+            files,
+            fails,
+            warnings: lints.len() - fails,
+            rules,
+        }
+    }
+}
+
+/// A self-contained, serializable projection of a [`LintError`]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SerializableLint {
+    /// Name of the rule that matched
+    pub rule: String,
+    /// Whether the lint fails the run
+    pub severity: Severity,
+    /// Name of the linted file
+    pub file: String,
+    /// Line number of the start of the match
+    pub start_line: usize,
+    /// Column of the start of the match
+    pub start_col: usize,
+    /// Line number of the end of the match
+    pub end_line: usize,
+    /// Column of the end of the match
+    pub end_col: usize,
+    /// Source text of the matched tokens
+    pub matched: String,
+    /// Description of the rule
+    pub message: String,
+    /// Suggested fix or alternative
+    pub help: Option<String>,
+    /// Link to more information
+    pub link: Option<String>,
+}
+
+impl LintError {
+    /// Projects the lint into a form that can be serialized and handed to other tools
+    pub fn to_serializable(&self) -> SerializableLint {
+        let span: CompilerSpan = self.into();
+
+        SerializableLint {
+            rule: self.rule.name.clone(),
+            severity: match self.fails {
+                true => Severity::Error,
+                false => Severity::Warning,
+            },
+            file: span.file_name,
+            start_line: span.line_start,
+            start_col: span.column_start,
+            end_line: span.line_end,
+            end_col: span.column_end,
+            matched: self.matched(),
+            message: self.to_string(),
+            help: self.help(),
+            link: self.link(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lint_str, ty::Rules};
+
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "No unwrap"
+            fails = true
+            link = "https://example.com/u"
+            range = [0, 3]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        )
+        .unwrap();
+        let lint =
+            lint_str("fn f() {\n    a.unwrap();\n}", "a.rs", &rules).unwrap()[0].to_serializable();
+
+        let json = serde_json::to_value(&lint).unwrap();
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["file"], "a.rs");
+        assert_eq!(
+            (&json["start_line"], &json["start_col"]),
+            (&2.into(), &6.into())
+        );
+        assert_eq!(json["matched"], ".unwrap()");
+        assert_eq!(json["link"], "https://example.com/u");
+
+        let back: SerializableLint = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), json);
+    }
+}
@@ -1,47 +1,112 @@
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
     error::Error,
     fmt::{Debug, Display},
-    ops::RangeInclusive,
+    hash::{Hash, Hasher},
+    io,
+    ops::{Range, RangeInclusive},
+    path::Path,
     sync::Arc,
 };
 
-use miette::{Diagnostic, LabeledSpan, NamedSource, SourceOffset, SourceSpan};
-use proc_macro2::{Delimiter, Span, TokenTree};
+use globset::{GlobBuilder, GlobMatcher};
+use indexmap::IndexMap;
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceSpan};
+use proc_macro2::{Delimiter, LineColumn, Span, TokenTree};
+use regex::{Regex, RegexBuilder};
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{de, Deserialize, Serialize};
 
+use crate::compiler::SuggestionApplicability;
+
+/// A brace block, named by the keyword that introduced it (e.g. `fn`), if any,
+/// or a macro call's arguments, named by the macro (e.g. `println!`)
+#[derive(Debug)]
+pub struct Scope {
+    pub kind: Option<String>,
+    pub parent: Option<Arc<Scope>>,
+}
+
+/// A token's location, resolved up front so it can be used away from the parsing thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenSpan {
+    start: LineColumn,
+    end: LineColumn,
+    bytes: Range<usize>,
+}
+
+impl TokenSpan {
+    /// The line (1-indexed) and column (0-indexed, in chars) the token starts at
+    pub fn start(&self) -> LineColumn {
+        self.start
+    }
+
+    /// The line and column the token ends at
+    pub fn end(&self) -> LineColumn {
+        self.end
+    }
+
+    /// The token's byte offsets in its source file
+    pub fn byte_range(&self) -> Range<usize> {
+        self.bytes.clone()
+    }
+}
+
+impl From<Span> for TokenSpan {
+    fn from(v: Span) -> TokenSpan {
+        TokenSpan {
+            start: v.start(),
+            end: v.end(),
+            bytes: v.byte_range(),
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct Named(String, String, Arc<Span>);
+pub struct Named(String, String, Arc<TokenSpan>, Option<Arc<Scope>>);
 impl Debug for Named {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(format!("{}(\"{}\")", self.0, self.1).as_str())
     }
 }
 
-unsafe impl Send for Named {}
-unsafe impl Sync for Named {}
-
-impl Into<Named> for proc_macro2::Ident {
-    fn into(self) -> Named {
-        Named("Ident".to_string(), self.to_string(), self.span().into())
+impl From<proc_macro2::Ident> for Named {
+    fn from(v: proc_macro2::Ident) -> Named {
+        Named(
+            "Ident".to_string(),
+            v.to_string(),
+            Arc::new(v.span().into()),
+            None,
+        )
     }
 }
 
-impl Into<Named> for proc_macro2::Punct {
-    fn into(self) -> Named {
-        Named("Punct".to_string(), self.to_string(), self.span().into())
+impl From<proc_macro2::Punct> for Named {
+    fn from(v: proc_macro2::Punct) -> Named {
+        Named(
+            "Punct".to_string(),
+            v.to_string(),
+            Arc::new(v.span().into()),
+            None,
+        )
     }
 }
 
-impl Into<Named> for proc_macro2::Literal {
-    fn into(self) -> Named {
-        Named("Literal".to_string(), self.to_string(), self.span().into())
+impl From<proc_macro2::Literal> for Named {
+    fn from(v: proc_macro2::Literal) -> Named {
+        Named(
+            "Literal".to_string(),
+            v.to_string(),
+            Arc::new(v.span().into()),
+            None,
+        )
     }
 }
 
-impl Into<Named> for proc_macro2::TokenTree {
-    fn into(self) -> Named {
-        match self {
+impl From<proc_macro2::TokenTree> for Named {
+    fn from(v: proc_macro2::TokenTree) -> Named {
+        match v {
             TokenTree::Ident(t) => t.into(),
             TokenTree::Punct(t) => t.into(),
             TokenTree::Literal(t) => t.into(),
@@ -51,129 +116,1464 @@ impl Into<Named> for proc_macro2::TokenTree {
 }
 
 impl Named {
+    pub fn new(kind: &str, value: String, span: Span) -> Named {
+        Named(kind.to_string(), value, Arc::new(span.into()), None)
+    }
+
+    /// A `BlankLine` token covering a whitespace-only line, given its 1-indexed number and text
+    pub fn blank_line(line: usize, text: &str, byte_start: usize) -> Named {
+        let span = TokenSpan {
+            start: LineColumn { line, column: 0 },
+            end: LineColumn {
+                line,
+                column: text.chars().count(),
+            },
+            bytes: byte_start..byte_start + text.len(),
+        };
+
+        Named("BlankLine".to_string(), String::new(), Arc::new(span), None)
+    }
+
     pub fn delim_pair(d: Delimiter, s1: Span, s2: Span) -> [Named; 2] {
         let [a, b] = match_delim(d);
         [
-            Named("Delim".to_string(), a.to_string(), s1.into()),
-            Named("Delim".to_string(), b.to_string(), s2.into()),
+            Named(
+                "Delim".to_string(),
+                a.to_string(),
+                Arc::new(s1.into()),
+                None,
+            ),
+            Named(
+                "Delim".to_string(),
+                b.to_string(),
+                Arc::new(s2.into()),
+                None,
+            ),
         ]
     }
 
-    pub fn span(&self) -> Arc<Span> {
-        self.2.clone()
+    /// The kind of token, e.g. `Ident`, `Punct` or `Delim`
+    pub fn kind(&self) -> &str {
+        &self.0
+    }
+
+    /// The token's source text
+    pub fn value(&self) -> &str {
+        &self.1
+    }
+
+    pub fn span(&self) -> Arc<TokenSpan> {
+        self.2.clone()
+    }
+
+    /// The innermost brace block the token sits in
+    pub fn scope(&self) -> Option<&Arc<Scope>> {
+        self.3.as_ref()
+    }
+
+    pub fn with_scope(mut self, scope: Option<Arc<Scope>>) -> Named {
+        self.3 = scope;
+        self
+    }
+
+    /// Whether the token sits in a brace block introduced by the given keyword, at any depth
+    pub fn in_scope(&self, kind: &str) -> bool {
+        let mut scope = self.scope();
+        while let Some(s) = scope {
+            if s.kind.as_deref() == Some(kind) {
+                return true;
+            }
+            scope = s.parent.as_ref();
+        }
+
+        false
+    }
+
+    /// Whether the token sits in a macro call's arguments, at any depth
+    pub fn in_macro(&self) -> bool {
+        let mut scope = self.scope();
+        while let Some(s) = scope {
+            if s.kind.as_deref().is_some_and(|k| k.ends_with('!')) {
+                return true;
+            }
+            scope = s.parent.as_ref();
+        }
+
+        false
+    }
+}
+
+/// Integer literal suffixes, also stripped from hex, octal and binary literals
+const INT_SUFFIXES: [&str; 12] = [
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Returns the value of an integer or float literal, ignoring `_` separators and any suffix
+pub fn literal_number(lit: &str) -> Option<f64> {
+    let lit = lit.replace('_', "");
+    let radix = match lit.get(..2) {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => 10,
+    };
+
+    let unsuffixed = |suffixes: &[&str]| {
+        suffixes
+            .iter()
+            .find_map(|s| lit.strip_suffix(s))
+            .unwrap_or(&lit)
+            .to_string()
+    };
+
+    if radix != 10 {
+        let digits = unsuffixed(&INT_SUFFIXES);
+        return u128::from_str_radix(&digits[2..], radix)
+            .ok()
+            .map(|n| n as f64);
+    }
+
+    let digits = unsuffixed(&[INT_SUFFIXES.as_slice(), &["f32", "f64"]].concat());
+    match digits.starts_with(|c: char| c.is_ascii_digit()) {
+        true => digits.parse().ok(),
+        false => None,
+    }
+}
+
+/// Compares a number against a bound like `>255`, `<=10` or `==0`, or a range like `1..10`
+/// or `1..=9`, returning `None` when the bound isn't one of those
+fn compare_number(bound: &str, n: f64) -> Option<bool> {
+    let bound = bound.trim();
+    for (op, cmp) in [
+        (">=", f64::ge as fn(&f64, &f64) -> bool),
+        ("<=", f64::le),
+        ("==", f64::eq),
+        ("!=", f64::ne),
+        (">", f64::gt),
+        ("<", f64::lt),
+    ] {
+        if let Some(v) = bound.strip_prefix(op) {
+            return v.trim().parse().ok().map(|v| cmp(&n, &v));
+        }
+    }
+
+    if let Some((start, end)) = bound.split_once("..=") {
+        let (start, end) = (
+            start.trim().parse::<f64>().ok()?,
+            end.trim().parse::<f64>().ok()?,
+        );
+        return Some((start..=end).contains(&n));
+    }
+
+    let (start, end) = bound.split_once("..")?;
+    let (start, end) = (
+        start.trim().parse::<f64>().ok()?,
+        end.trim().parse::<f64>().ok()?,
+    );
+    Some((start..end).contains(&n))
+}
+
+/// Returns the contents of a string, byte string or char literal with escapes resolved
+pub fn unescape_literal(lit: &str) -> Option<String> {
+    let lit = lit.strip_prefix(['b', 'c']).unwrap_or(lit);
+
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.chars().take_while(|c| *c == '#').count();
+        return raw[hashes..]
+            .strip_prefix('"')?
+            .strip_suffix(&format!("\"{}", "#".repeat(hashes)))
+            .map(|v| v.to_string());
+    }
+
+    let body = lit
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| lit.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))?;
+
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            'x' => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                out.push(u8::from_str_radix(&hex, 16).ok()? as char);
+            }
+            'u' => {
+                let hex = chars
+                    .by_ref()
+                    .skip_while(|c| *c == '{')
+                    .take_while(|c| *c != '}')
+                    .collect::<String>();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            c => out.push(c),
+        }
+    }
+
+    Some(out)
+}
+
+fn match_delim(d: Delimiter) -> [char; 2] {
+    match d {
+        Delimiter::Parenthesis => ['(', ')'],
+        Delimiter::Brace => ['{', '}'],
+        Delimiter::Bracket => ['[', ']'],
+        Delimiter::None => [' ', ' '],
+    }
+}
+
+/// Counts the comma-separated items in the group opened at the given token, up to its balanced close
+fn group_items(s: &[Named], open: usize) -> Option<usize> {
+    if s.get(open)?.0 != "Delim" || !["(", "[", "{"].contains(&s[open].1.as_str()) {
+        return None;
+    }
+
+    let (mut depth, mut items, mut empty) = (0, 0, true);
+    for t in &s[open + 1..] {
+        match (t.0.as_str(), t.1.as_str()) {
+            ("Delim", "(" | "[" | "{") => depth += 1,
+            ("Delim", ")" | "]" | "}") if depth == 0 => return Some(items + usize::from(!empty)),
+            ("Delim", ")" | "]" | "}") => depth -= 1,
+            ("Punct", ",") if depth == 0 => {
+                items += 1;
+                empty = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        empty = false;
+    }
+
+    None
+}
+
+/* ----------------- */
+
+/// A token kind and value to match, with any regex flags (`i`, `m`, `s` or `x`) for regex values
+#[derive(Clone, PartialEq)]
+pub struct Needle(pub Kinds, pub Option<String>, pub String);
+
+/// The kinds a needle accepts, one kind like `"Ident"` or a set like `["Ident", "Literal"]`
+#[derive(Deserialize, Serialize, JsonSchema, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Kinds {
+    One(String),
+    Any(Vec<String>),
+}
+
+impl Kinds {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        match self {
+            Kinds::One(k) => std::slice::from_ref(k).iter(),
+            Kinds::Any(ks) => ks.iter(),
+        }
+        .map(String::as_str)
+    }
+}
+
+impl Display for Kinds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.iter().collect::<Vec<_>>().join("|"))
+    }
+}
+
+/// The object form of a needle, e.g. `{ "kind": "Ident", "value": "^foo", "regex": true }`
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct NeedleObject {
+    kind: Kinds,
+    value: Option<String>,
+    /// Treats the value as a regex, as if it were surrounded by `/`
+    #[serde(default)]
+    regex: bool,
+    /// Regex flags, any of `i`, `m`, `s` and `x`
+    #[serde(default)]
+    flags: String,
+}
+
+/// A `[kind, value]` pair, or a needle object, as read by [`Needle`]'s deserializer
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum NeedleForm {
+    Pair(Kinds, Option<String>),
+    Object(NeedleObject),
+}
+
+impl JsonSchema for Needle {
+    fn schema_name() -> String {
+        "Needle".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        NeedleForm::json_schema(gen)
+    }
+}
+
+/// Writes a needle as a `[kind, value]` pair, or an object when it has regex flags
+impl Serialize for Needle {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.2.is_empty() {
+            true => (&self.0, &self.1).serialize(serializer),
+            false => NeedleObject {
+                kind: self.0.clone(),
+                value: self.1.clone(),
+                regex: false,
+                flags: self.2.clone(),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+/// Reads a needle either as a `[kind, value]` pair, or a needle object
+impl<'de> Deserialize<'de> for Needle {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NeedleVisitor;
+        impl<'de> de::Visitor<'de> for NeedleVisitor {
+            type Value = Needle;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a [kind, value] pair, or a needle object")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let (kind, value) = <(Kinds, Option<String>)>::deserialize(
+                    de::value::SeqAccessDeserializer::new(seq),
+                )?;
+                Ok(Needle(kind, value, String::new()))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let o = NeedleObject::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                let value = match o.regex {
+                    true => o.value.map(|v| format!("/{v}/")),
+                    false => o.value,
+                };
+                Ok(Needle(o.kind, value, o.flags))
+            }
+        }
+
+        deserializer.deserialize_any(NeedleVisitor)
+    }
+}
+
+impl Debug for Needle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "{}(\"{}\"{})",
+            self.0,
+            self.1.clone().unwrap_or_default(),
+            self.2
+        ))
+    }
+}
+
+/// Strict and reserved Rust keywords, matched by the `Keyword` needle
+pub const KEYWORDS: [&str; 51] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+thread_local! {
+    /// Compiled needle regexes by pattern and flags, so each is only built once per thread
+    static REGEXES: RefCell<HashMap<(String, String), Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Builds a regex with needle flags, `i`, `m`, `s` and `x`
+fn build_regex(pattern: &str, flags: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(flags.contains('i'))
+        .multi_line(flags.contains('m'))
+        .dot_matches_new_line(flags.contains('s'))
+        .ignore_whitespace(flags.contains('x'))
+        .build()
+}
+
+/// Tests text against a regex, compiling and caching it on first use, and recording named groups
+fn regex_match(
+    pattern: &str,
+    flags: &str,
+    text: &str,
+    captures: &mut HashMap<String, String>,
+) -> bool {
+    REGEXES.with(|r| {
+        let mut r = r.borrow_mut();
+        let re = r
+            .entry((pattern.to_string(), flags.to_string()))
+            .or_insert_with(|| build_regex(pattern, flags).unwrap());
+
+        let Some(c) = re.captures(text) else {
+            return false;
+        };
+
+        for name in re.capture_names().flatten() {
+            if let Some(m) = c.name(name) {
+                captures.insert(name.to_string(), m.as_str().to_string());
+            }
+        }
+
+        true
+    })
+}
+
+/// Tests whether a token is of a single needle kind
+fn kind_matches(kind: &str, s: &Named) -> bool {
+    match kind {
+        "Keyword" => s.0 == "Ident" && KEYWORDS.contains(&s.1.as_str()),
+        "Str" => s.0 == "Literal" && unescape_literal(&s.1).is_some(),
+        "Number" => s.0 == "Literal" && literal_number(&s.1).is_some(),
+        "Unraw" => s.0 == "Ident",
+        "StmtEnd" => (s.0 == "Punct" && s.1 == ";") || (s.0 == "Delim" && s.1 == "}"),
+        "Group" => s.0 == "Delim" && matches!(s.1.as_str(), "(" | "{" | "["),
+        k => s.0 == k,
+    }
+}
+
+impl Needle {
+    /// Tests whether a token is of a kind this needle looks for, ignoring its value
+    pub fn test_kind(&self, s: &Named) -> bool {
+        self.0.iter().any(|k| kind_matches(k, s))
+    }
+
+    /// The token kinds this needle's kinds can match
+    pub fn token_kinds(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        for k in self.0.iter() {
+            let kinds = match k {
+                "Keyword" | "Unraw" => vec!["Ident"],
+                "Str" | "Number" => vec!["Literal"],
+                "StmtEnd" => vec!["Punct", "Delim"],
+                "Group" => vec!["Delim"],
+                k => vec![k],
+            };
+
+            for k in kinds {
+                if !out.contains(&k) {
+                    out.push(k);
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn test(&self, s: &Named) -> bool {
+        self.test_with(s, &mut HashMap::new())
+    }
+
+    /// The text a needle value is compared against, the unescaped contents for `Str`,
+    /// the identifier without any `r#` for `Unraw` and the delimiter's name for `Group`
+    fn text(kind: &str, s: &Named) -> String {
+        match kind {
+            "Group" => match s.1.as_str() {
+                "(" => "Parenthesis",
+                "{" => "Brace",
+                _ => "Bracket",
+            }
+            .to_string(),
+            "Str" => unescape_literal(&s.1).unwrap_or_default(),
+            "Unraw" => s.1.strip_prefix("r#").unwrap_or(&s.1).to_string(),
+            _ => s.1.clone(),
+        }
+    }
+
+    /// The needle's regex, for values surrounded by `/`
+    pub fn regex(&self) -> Option<&str> {
+        let v = self.1.as_deref()?;
+        (v.len() > 1 && v.starts_with('/') && v.ends_with('/')).then(|| &v[1..v.len() - 1])
+    }
+
+    /// Describes a needle regex that doesn't compile, or bad flags
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if let Some(Err(e)) = self.regex().map(|re| build_regex(re, &self.2)) {
+            out.push(format!("has an invalid regex: {e}"));
+        }
+
+        if let Some(f) = self.2.chars().find(|f| !"imsx".contains(*f)) {
+            out.push(format!("has an unknown regex flag `{f}`"));
+        } else if !self.2.is_empty() && self.regex().is_none() {
+            out.push("has regex flags but no regex".to_string());
+        }
+
+        out
+    }
+
+    /// Tests a token, recording `$N` captures and named regex groups,
+    /// and checking `\N` backreferences against them
+    pub fn test_with(&self, s: &Named, captures: &mut HashMap<String, String>) -> bool {
+        self.0
+            .iter()
+            .any(|k| kind_matches(k, s) && self.test_value(k, s, captures))
+    }
+
+    /// Tests a token's value as seen by one of the needle's kinds
+    fn test_value(&self, kind: &str, s: &Named, captures: &mut HashMap<String, String>) -> bool {
+        let text = Self::text(kind, s);
+        if let Some(v) = &self.1 {
+            if let Some(n) = v.strip_prefix('$').and_then(|n| n.parse::<usize>().ok()) {
+                captures.insert(n.to_string(), text);
+                return true;
+            }
+
+            if let Some(n) = v.strip_prefix('\\').and_then(|n| n.parse::<usize>().ok()) {
+                return captures.get(&n.to_string()) == Some(&text);
+            }
+
+            if kind == "Number" {
+                if let Some(matches) = literal_number(&text).and_then(|n| compare_number(v, n)) {
+                    return matches;
+                }
+            }
+        }
+
+        if let Some(v) = &self.1 {
+            if let Some(re) = self.regex() {
+                regex_match(re, &self.2, &text, captures)
+            } else {
+                text == *v
+            }
+        } else {
+            true
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    pub description: String,
+    pub help: Option<String>,
+    /// Background context, shown apart from the actionable help
+    pub note: Option<String>,
+    #[serde(deserialize_with = "deser_range_from_array")]
+    #[schemars(with = "[usize; 2]")]
+    pub range: RangeInclusive<usize>,
+    #[serde(default)]
+    pub pattern: Vec<Needle>,
+    /// Match runs of this many tokens repeating an earlier run in the file, instead of a pattern
+    pub repeated_tokens: Option<usize>,
+    pub link: Option<String>,
+    #[serde(default)]
+    pub fails: bool,
+    pub replace: Option<String>,
+    /// Orders lints starting at the same place, higher first
+    #[serde(default)]
+    pub priority: i32,
+    /// Lets `--fix` apply the rule's machine applicable suggestion, rather than only showing it
+    #[serde(default)]
+    pub fixable: bool,
+    /// A short label for the first suggestion shown by editors, e.g. "Replace with expect"
+    pub fix_title: Option<String>,
+    /// Replacements for the match, each with its own applicability
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Globs of the files the rule runs on, matched against the path or the file name
+    #[serde(default)]
+    pub applies_to: Vec<String>,
+    pub span_lines: Option<SpanLines>,
+    /// Skip matches touching lines longer than this, overriding the global option
+    pub max_line_length: Option<usize>,
+    /// Only report from the Nth match in a file onwards
+    pub min_occurrences: Option<usize>,
+    /// Only match inside a brace block introduced by this keyword, e.g. `fn`
+    pub scope: Option<String>,
+    /// Only match when the group opened by the pattern's last token has more comma-separated items
+    pub max_items: Option<usize>,
+    /// Only match when the first matched token is the first on its line
+    #[serde(default)]
+    pub line_leading: bool,
+    /// Needles whose token must directly follow the previous one, with nothing in between
+    #[serde(default)]
+    pub adjacent: Vec<usize>,
+    /// Only match when the token after the match isn't this, without including it in the match
+    pub not_followed_by: Option<Needle>,
+    /// Highlight only this inclusive range of characters within the ranged tokens' text
+    pub range_chars: Option<[usize; 2]>,
+    /// Skip matches with tokens inside macro call arguments, e.g. `println!(...)`
+    #[serde(default)]
+    pub skip_macros: bool,
+}
+
+/// A replacement for a match, and how safely it can be applied
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Suggestion {
+    pub replace: String,
+    #[serde(default)]
+    pub applicability: SuggestionApplicability,
+}
+
+/// Constrains how many source lines a match may cover
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanLines {
+    /// The match must sit on one line
+    Single,
+    /// The match must cover more than one line
+    Multi,
+    /// The match must cover an inclusive range of lines
+    #[serde(untagged, deserialize_with = "deser_range_from_array")]
+    #[schemars(with = "[usize; 2]")]
+    Range(RangeInclusive<usize>),
+}
+
+impl SpanLines {
+    pub fn allows(&self, window: &[Named]) -> bool {
+        let (Some(first), Some(last)) = (window.first(), window.last()) else {
+            return false;
+        };
+
+        let lines = last.span().end().line - first.span().start().line + 1;
+        match self {
+            SpanLines::Single => lines == 1,
+            SpanLines::Multi => lines > 1,
+            SpanLines::Range(r) => r.contains(&lines),
+        }
+    }
+}
+
+impl Rule {
+    /// A rule with nothing but a name and description, e.g. for a [`crate::CustomRule`]'s lints
+    pub fn new(name: &str, description: &str) -> Rule {
+        Rule {
+            name: name.to_string(),
+            description: description.to_string(),
+            help: None,
+            note: None,
+            range: 0..=0,
+            pattern: vec![],
+            repeated_tokens: None,
+            link: None,
+            fails: false,
+            replace: None,
+            priority: 0,
+            fixable: false,
+            fix_title: None,
+            suggestions: vec![],
+            tags: vec![],
+            applies_to: vec![],
+            span_lines: None,
+            max_line_length: None,
+            min_occurrences: None,
+            scope: None,
+            max_items: None,
+            line_leading: false,
+            adjacent: vec![],
+            not_followed_by: None,
+            range_chars: None,
+            skip_macros: false,
+        }
+    }
+
+    /// The number of tokens in each match
+    pub fn window_len(&self) -> usize {
+        self.repeated_tokens.unwrap_or(self.pattern.len())
+    }
+
+    /// Describes anything that would stop the rule matching: an empty pattern,
+    /// a range outside of it, or a regex that doesn't compile
+    pub fn problems(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        match self.repeated_tokens {
+            Some(0) => out.push("repeated_tokens must be at least 1".to_string()),
+            Some(_) if !self.pattern.is_empty() => {
+                out.push("has both a pattern and repeated_tokens".to_string())
+            }
+            None if self.pattern.is_empty() => out.push("pattern is empty".to_string()),
+            _ => {}
+        }
+
+        if self.range.start() > self.range.end() || *self.range.end() >= self.window_len() {
+            out.push(format!(
+                "range [{}, {}] is out of bounds for a {} token pattern",
+                self.range.start(),
+                self.range.end(),
+                self.window_len()
+            ));
+        }
+
+        for (i, n) in self.pattern.iter().enumerate() {
+            out.extend(n.problems().into_iter().map(|e| format!("needle {i} {e}")));
+        }
+
+        if let Some(n) = &self.not_followed_by {
+            out.extend(
+                n.problems()
+                    .into_iter()
+                    .map(|e| format!("not_followed_by {e}")),
+            );
+        }
+
+        for i in &self.adjacent {
+            if *i == 0 || *i >= self.pattern.len() {
+                out.push(format!(
+                    "adjacent needle {i} has no previous needle in a {} token pattern",
+                    self.pattern.len()
+                ));
+            }
+        }
+
+        if let Some([start, end]) = self.range_chars {
+            if start > end {
+                out.push(format!("range_chars [{start}, {end}] is backwards"));
+            }
+        }
+
+        for g in &self.applies_to {
+            if let Err(e) = glob(g) {
+                out.push(format!("applies_to glob '{g}' is invalid: {e}"));
+            }
+        }
+
+        out
+    }
+
+    /// Expands `${VAR}` environment variables in the rule's link and help,
+    /// describing any variables that aren't set
+    pub fn expand_env(&mut self) -> Vec<String> {
+        let mut out = Vec::new();
+        for field in [&mut self.link, &mut self.help].into_iter().flatten() {
+            match expand_env(field) {
+                Ok(v) => *field = v,
+                Err(e) => out.push(e),
+            }
+        }
+
+        out
+    }
+
+    /// Whether the rule runs on a file, by its `applies_to` globs
+    pub fn applies_to(&self, file: &str) -> bool {
+        let path = Path::new(file);
+        self.applies_to.is_empty()
+            || self.applies_to.iter().any(|g| {
+                glob(g).is_ok_and(|p| {
+                    p.is_match(path) || path.file_name().is_some_and(|n| p.is_match(n))
+                })
+            })
+    }
+
+    /// The rule's suggestions, with `replace` first as a machine applicable one
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        let replace = self.replace.clone().map(|replace| Suggestion {
+            replace,
+            applicability: SuggestionApplicability::MachineApplicable,
+        });
+
+        replace
+            .into_iter()
+            .chain(self.suggestions.iter().cloned())
+            .collect()
+    }
+
+    pub fn test(&self, s: &[Named]) -> Result<(), Vec<Named>> {
+        match self.matches(s).into_iter().next() {
+            Some(window) => Err(window),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds all non-overlapping matches in a token list, in order
+    pub fn matches(&self, s: &[Named]) -> Vec<Vec<Named>> {
+        if self.repeated_tokens.is_some() {
+            return self.repeats(s).into_iter().map(|(w, _)| w).collect();
+        }
+
+        let first = &self.pattern[0];
+        self.matches_from(s, (0..s.len()).filter(|m| first.test(&s[*m])))
+    }
+
+    /// Finds non-overlapping runs of `repeated_tokens` tokens with the same kinds and values
+    /// as an earlier run, paired with the first run
+    pub fn repeats(&self, s: &[Named]) -> Vec<(Vec<Named>, Vec<Named>)> {
+        let n = self.repeated_tokens.unwrap_or_default();
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let key = |w: &[Named]| {
+            let mut h = DefaultHasher::new();
+            w.iter().for_each(|t| (t.kind(), t.value()).hash(&mut h));
+            h.finish()
+        };
+        let same = |a: &[Named], b: &[Named]| {
+            a.iter()
+                .zip(b)
+                .all(|(a, b)| a.kind() == b.kind() && a.value() == b.value())
+        };
+
+        let mut first = HashMap::new();
+        let mut out = Vec::new();
+        let mut next = 0;
+        for (i, w) in s.windows(n).enumerate() {
+            let earlier = *first.entry(key(w)).or_insert(i);
+            if i >= next && earlier + n <= i && same(w, &s[earlier..earlier + n]) {
+                out.push((w.to_vec(), s[earlier..earlier + n].to_vec()));
+                next = i + n;
+            }
+        }
+
+        out
+    }
+
+    /// Finds all non-overlapping matches starting at any of the given, ascending, token indices
+    pub fn matches_from(
+        &self,
+        s: &[Named],
+        candidates: impl IntoIterator<Item = usize>,
+    ) -> Vec<Vec<Named>> {
+        self.matches_from_where(s, candidates, |_| true)
+    }
+
+    /// Finds all non-overlapping matches starting at any of the given, ascending, token indices,
+    /// skipping windows rejected by `keep`, e.g. for constraints that need the source text
+    pub fn matches_from_where(
+        &self,
+        s: &[Named],
+        candidates: impl IntoIterator<Item = usize>,
+        keep: impl Fn(&[Named]) -> bool,
+    ) -> Vec<Vec<Named>> {
+        let mut out = Vec::new();
+        let mut next = 0;
+
+        for m in candidates {
+            if m < next {
+                continue;
+            }
+
+            let Some(window) = self.test_at(s, m).filter(|w| keep(w)) else {
+                continue;
+            };
+
+            // Continue from the first token starting after the end of the match
+            let end = window.last().unwrap().span().end();
+            next = s[m + 1..]
+                .iter()
+                .position(|v| {
+                    let start = v.span().start();
+                    start.line > end.line || (start.line == end.line && start.column >= end.column)
+                })
+                .map_or(s.len(), |i| m + 1 + i);
+
+            out.push(window);
+        }
+
+        out
+    }
+
+    /// The `$N` captures and named regex groups recorded while matching a window
+    pub fn captures(&self, window: &[Named]) -> HashMap<String, String> {
+        let mut captures = HashMap::new();
+        window.iter().zip(self.pattern.iter()).for_each(|(a, b)| {
+            b.test_with(a, &mut captures);
+        });
+
+        captures
+    }
+
+    /// Tests for a match starting at the given token
+    pub fn test_at(&self, s: &[Named], m: usize) -> Option<Vec<Named>> {
+        let window = s.get(m..m + self.pattern.len())?;
+
+        let mut captures = HashMap::new();
+        if !window
+            .iter()
+            .zip(self.pattern.iter())
+            .all(|(a, b)| b.test_with(a, &mut captures))
+        {
+            return None;
+        }
+
+        if let (Some(n), Some(next)) = (&self.not_followed_by, s.get(m + self.pattern.len())) {
+            if n.test_with(next, &mut captures) {
+                return None;
+            }
+        }
+
+        if self.span_lines.as_ref().is_some_and(|l| !l.allows(window)) {
+            return None;
+        }
+
+        if self.scope.as_ref().is_some_and(|k| !window[0].in_scope(k)) {
+            return None;
+        }
+
+        if self.skip_macros && window.iter().any(Named::in_macro) {
+            return None;
+        }
+
+        if let Some(max) = self.max_items {
+            if group_items(s, m + self.pattern.len() - 1).is_none_or(|n| n <= max) {
+                return None;
+            }
+        }
+
+        if !self.adjacent.iter().all(|i| {
+            let (Some(prev), Some(t)) =
+                (i.checked_sub(1).and_then(|p| window.get(p)), window.get(*i))
+            else {
+                return false;
+            };
+            prev.span().byte_range().end == t.span().byte_range().start
+        }) {
+            return None;
+        }
+
+        Some(window.to_vec())
+    }
+}
+
+/// Options controlling how source files are tokenized and matched
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Options {
+    /// Reconstruct `#[...]` attributes into `Attr` tokens named by their path
+    #[serde(default)]
+    pub attributes: bool,
+    /// Surface doc comments as `Doc` tokens holding their text
+    #[serde(default)]
+    pub docs: bool,
+    /// Join `'` and the following identifier into `Lifetime` tokens holding the name
+    #[serde(default)]
+    pub lifetimes: bool,
+    /// Join multi-character operators like `..=` into `Op` tokens, rather than single puncts
+    #[serde(default)]
+    pub operators: bool,
+    /// Add `BlankLine` tokens for whitespace-only lines outside of tokens, for rules on spacing
+    #[serde(default)]
+    pub blank_lines: bool,
+    /// Stop reporting a rule after this many matches in a single file
+    pub max_per_rule: Option<usize>,
+    /// Skip matches touching lines longer than this, e.g. generated or minified code
+    pub max_line_length: Option<usize>,
+}
+
+impl Options {
+    /// Merges another set of options into these, enabling anything either enables
+    pub fn merge(&mut self, other: Options) {
+        self.attributes |= other.attributes;
+        self.docs |= other.docs;
+        self.lifetimes |= other.lifetimes;
+        self.operators |= other.operators;
+        self.blank_lines |= other.blank_lines;
+        self.max_per_rule = other.max_per_rule.or(self.max_per_rule);
+        self.max_line_length = other.max_line_length.or(self.max_line_length);
+    }
+}
+
+/// Rules that only apply to files matching a set of globs
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    pub paths: Vec<String>,
+    #[serde(default, deserialize_with = "deser_rules")]
+    #[schemars(with = "RuleSet")]
+    pub rules: IndexMap<String, Rule>,
+    /// Whether existing rules fail in matching files, by rule name, without redefining them
+    #[serde(default)]
+    pub fails: IndexMap<String, bool>,
+}
+
+impl Override {
+    pub fn matches(&self, file: &str) -> bool {
+        self.paths
+            .iter()
+            .any(|p| glob(p).is_ok_and(|p| p.is_match(file)))
+    }
+}
+
+/// Compiles a glob the way every glob in splint is read: like a shell, `*` stays within a directory,
+/// `**` matches any number of directories, and `{a,b}` either alternative
+pub fn glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|g| g.compile_matcher())
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    #[serde(deserialize_with = "deser_rules")]
+    #[schemars(with = "RuleSet")]
+    pub rules: IndexMap<String, Rule>,
+    #[serde(default)]
+    pub options: Options,
+    #[serde(default)]
+    pub overrides: Vec<Override>,
+}
+
+impl Rules {
+    /// Merges another set of rules into this one, overriding rules with the same name
+    pub fn merge(&mut self, other: Rules) {
+        self.rules.extend(other.rules);
+        self.options.merge(other.options);
+        self.overrides.extend(other.overrides);
+    }
+
+    /// Builds the effective rules for a file, layering matching overrides over the base rules
+    pub fn for_file(&self, file: &str) -> Rules {
+        let mut rules = Rules {
+            rules: self.rules.clone(),
+            options: self.options.clone(),
+            overrides: vec![],
+        };
+
+        for o in self.overrides.iter().filter(|o| o.matches(file)) {
+            rules.rules.extend(o.rules.clone());
+            for (name, fails) in &o.fails {
+                if let Some(r) = rules.rules.get_mut(name) {
+                    r.fails = *fails;
+                }
+            }
+        }
+
+        rules
+    }
+
+    /// Describes override `fails` entries naming rules that don't exist,
+    /// checked once every rules file is merged as they can refer to rules from another
+    pub fn unknown_overridden(&self) -> Vec<String> {
+        self.overrides
+            .iter()
+            .flat_map(|o| {
+                o.fails
+                    .keys()
+                    .filter(|name| {
+                        !self.rules.contains_key(*name)
+                            && !self.overrides.iter().any(|o| o.rules.contains_key(*name))
+                    })
+                    .map(move |name| format!("Override for {:?}: no rule named '{name}'", o.paths))
+            })
+            .collect()
+    }
+
+    /// Describes rules that can never report on their own, as another rule's pattern
+    /// is identical to or a prefix of theirs. Rules with match constraints are skipped.
+    pub fn shadowed(&self) -> Vec<String> {
+        let mut rules = self
+            .rules
+            .values()
+            .filter(|r| {
+                r.span_lines.is_none()
+                    && r.scope.is_none()
+                    && r.min_occurrences.is_none()
+                    && r.max_items.is_none()
+                    && !r.line_leading
+                    && r.adjacent.is_empty()
+                    && r.not_followed_by.is_none()
+                    && !r.skip_macros
+                    && r.repeated_tokens.is_none()
+            })
+            .collect::<Vec<_>>();
+        rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut out = Vec::new();
+        for (i, a) in rules.iter().enumerate() {
+            for b in rules.iter().skip(i + 1) {
+                if a.pattern == b.pattern {
+                    out.push(format!(
+                        "Rules '{}' and '{}' have identical patterns",
+                        a.name, b.name
+                    ));
+                } else if b.pattern.starts_with(&a.pattern) {
+                    out.push(format!(
+                        "Rule '{}' matches everywhere '{}' does",
+                        a.name, b.name
+                    ));
+                } else if a.pattern.starts_with(&b.pattern) {
+                    out.push(format!(
+                        "Rule '{}' matches everywhere '{}' does",
+                        b.name, a.name
+                    ));
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Describes why a raw document isn't shaped like a rules file at all, e.g. a top-level array
+    pub fn find_invalid_shape(value: &serde_json::Value) -> Option<String> {
+        let Some(map) = value.as_object() else {
+            return Some(format!(
+                "expected an object with a `rules` key, found {}",
+                json_kind(value)
+            ));
+        };
+
+        match map.get("rules") {
+            None if map.is_empty() => Some("missing a `rules` key".to_string()),
+            None => Some(format!(
+                "missing a `rules` key, found {}",
+                map.keys()
+                    .map(|k| format!("`{k}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Some(r) if !r.is_object() && !r.is_array() => Some(format!(
+                "`rules` should be a map or list of rules, found {}",
+                json_kind(r)
+            )),
+            Some(_) => None,
+        }
+    }
+
+    /// Finds the first rule in a raw rules document that fails to parse, describing why
+    pub fn find_invalid_rule(value: &serde_json::Value) -> Option<String> {
+        let overrides = value
+            .get("overrides")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|o| o.get("rules"));
+
+        std::iter::once(value.get("rules")?)
+            .chain(overrides)
+            .flat_map(|r| match r {
+                serde_json::Value::Object(map) => map
+                    .iter()
+                    .map(|(name, rule)| (name.clone(), rule))
+                    .collect::<Vec<_>>(),
+                serde_json::Value::Array(list) => list
+                    .iter()
+                    .enumerate()
+                    .map(
+                        |(i, rule)| match rule.get("name").and_then(|n| n.as_str()) {
+                            Some(name) => (name.to_string(), rule),
+                            None => (format!("#{}", i + 1), rule),
+                        },
+                    )
+                    .collect(),
+                _ => vec![],
+            })
+            .find_map(|(name, rule)| {
+                Rule::deserialize(rule)
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut r| {
+                        let problems = r.problems().into_iter().chain(r.expand_env());
+                        match problems.collect::<Vec<_>>().as_slice() {
+                            [] => Ok(()),
+                            problems => Err(problems.join(", ")),
+                        }
+                    })
+                    .err()
+                    .map(|e| format!("Rule '{name}': {e}"))
+            })
+    }
+
+    /// Keeps only the rules, including override rules, carrying at least one of the given tags
+    pub fn retain_tags(&mut self, tags: &[String]) {
+        if tags.is_empty() {
+            return;
+        }
+
+        let tagged = |_: &String, r: &mut Rule| r.tags.iter().any(|t| tags.contains(t));
+        self.rules.retain(tagged);
+        for o in self.overrides.iter_mut() {
+            o.rules.retain(tagged);
+        }
+    }
+}
+
+/// Failure to lint a file, as opposed to a lint found within it
+#[derive(Debug)]
+pub enum LintRunError {
+    /// The file couldn't be read
+    Io { file: String, error: io::Error },
+    /// The file couldn't be tokenized
+    Parse { file: String, message: String },
+}
+
+impl Display for LintRunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintRunError::Io { file, error } => write!(f, "Couldn't read {file}: {error}"),
+            LintRunError::Parse { file, message } => write!(f, "Couldn't parse {file}: {message}"),
+        }
+    }
+}
+
+impl Error for LintRunError {}
+
+/// Source text that isn't a valid token stream, e.g. with an unclosed delimiter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenizeError {
+    pub message: String,
+    /// 1-indexed line and char column the error was found at
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for TokenizeError {}
+
+#[derive(Debug, Clone)]
+pub struct LintError {
+    pub rule: Rule,
+    pub fails: bool,
+    /// Whether further matches of the rule in this file were dropped after this one
+    pub truncated: bool,
+    /// Where the highlighted part of the match sits in the source
+    pub position: Position,
+    pub window: Vec<Named>,
+    pub source: NamedSource<Arc<String>>,
+    /// The rule's note, with placeholders filled
+    pub note: Option<Note>,
+    /// Where the tokens first appeared, for `repeated_tokens` rules
+    pub first_copy: Option<FirstCopy>,
+}
+
+/// The location of a lint's highlighted tokens, with 1-indexed lines and char columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+    /// Byte offsets of the highlight in the source
+    pub byte_start: usize,
+    pub byte_end: usize,
+    /// Text of the line the highlight starts on
+    pub line_text: String,
+    /// Byte offset of the start of that line
+    pub line_byte_start: usize,
+}
+
+impl Position {
+    /// Locates highlighted tokens, given the source's lines and the byte offsets they start at
+    pub fn new(highlight: &[Named], lines: &[&str], line_starts: &[usize]) -> Position {
+        let (first, last) = (highlight[0].span(), highlight[highlight.len() - 1].span());
+        let line = first.start().line - 1;
+
+        Position {
+            start_line: first.start().line,
+            start_col: first.start().column + 1,
+            end_line: last.end().line,
+            end_col: last.end().column + 1,
+            byte_start: first.byte_range().start,
+            byte_end: last.byte_range().end,
+            line_text: lines.get(line).copied().unwrap_or_default().to_string(),
+            line_byte_start: line_starts.get(line).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Narrows the highlight to an inclusive range of characters within it,
+    /// leaving it as is if the range starts past its end
+    pub fn narrow(
+        self,
+        chars: [usize; 2],
+        source: &str,
+        lines: &[&str],
+        line_starts: &[usize],
+    ) -> Position {
+        let text = &source[self.byte_start..self.byte_end];
+        let Some(start) = text.char_indices().nth(chars[0]).map(|(i, _)| i) else {
+            return self;
+        };
+        let end = text
+            .char_indices()
+            .nth(chars[1] + 1)
+            .map_or(text.len(), |(i, _)| i)
+            .max(start);
+
+        // 1-indexed line and char column of a byte offset
+        let locate = |byte: usize| {
+            let line = line_starts
+                .partition_point(|s| *s <= byte)
+                .saturating_sub(1);
+            let start = line_starts.get(line).copied().unwrap_or_default();
+            (line, source[start..byte].chars().count() + 1)
+        };
+
+        let (byte_start, byte_end) = (self.byte_start + start, self.byte_start + end);
+        let ((line, start_col), (end_line, end_col)) = (locate(byte_start), locate(byte_end));
+        Position {
+            start_line: line + 1,
+            start_col,
+            end_line: end_line + 1,
+            end_col,
+            byte_start,
+            byte_end,
+            line_text: lines.get(line).copied().unwrap_or_default().to_string(),
+            line_byte_start: line_starts.get(line).copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Background context for a lint, rendered apart from its help
+#[derive(Debug, Clone)]
+pub struct Note(pub String);
+
+impl Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for Note {}
+impl Diagnostic for Note {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Advice)
+    }
+}
+
+/// The first run of tokens a `repeated_tokens` lint repeats, shown alongside it
+#[derive(Debug, Clone)]
+pub struct FirstCopy {
+    pub position: Position,
+    pub source: NamedSource<Arc<String>>,
+}
+
+impl Display for FirstCopy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "First seen on line {}", self.position.start_line)
+    }
+}
+
+impl Error for FirstCopy {}
+impl Diagnostic for FirstCopy {
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(miette::Severity::Advice)
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.source)
     }
-}
 
-fn match_delim(d: Delimiter) -> [char; 2] {
-    match d {
-        Delimiter::Parenthesis => ['(', ')'],
-        Delimiter::Brace => ['{', '}'],
-        Delimiter::Bracket => ['[', ']'],
-        Delimiter::None => [' ', ' '],
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(
+            LabeledSpan::new_primary_with_span(None, span(&self.position)),
+        )))
     }
 }
 
-/* ----------------- */
+impl LintError {
+    /// A lint highlighting all of a non-empty run of tokens from the source,
+    /// e.g. for a [`crate::CustomRule`]
+    pub fn new(rule: Rule, window: Vec<Named>, source: &str, file_name: &str) -> LintError {
+        let lines = source.lines().collect::<Vec<_>>();
+        let position = Position::new(&window, &lines, &crate::line_starts(source));
+        let mut e = LintError {
+            fails: rule.fails,
+            truncated: false,
+            rule,
+            position,
+            window,
+            source: NamedSource::new(file_name, Arc::new(source.to_string())),
+            note: None,
+            first_copy: None,
+        };
+        e.note = e.rule.note.as_deref().map(|v| Note(e.interpolate(v)));
+        e
+    }
 
-#[derive(Deserialize, Serialize, Clone)]
-pub struct Needle(pub String, pub Option<String>);
-impl Debug for Needle {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!(
-            "{}(\"{}\")",
-            self.0,
-            self.1.clone().unwrap_or_default()
-        ))
+    /// Where the whole match sits in the source, as replaced by its suggestions,
+    /// rather than just its highlighted range
+    pub fn match_position(&self) -> Position {
+        let source = self.source.inner();
+        let lines = source.lines().collect::<Vec<_>>();
+        Position::new(&self.window, &lines, &crate::line_starts(source))
     }
-}
 
-impl Needle {
-    pub fn test(&self, s: &Named) -> bool {
-        s.0 == self.0 && {
-            if let Some(v) = &self.1 {
-                if v.starts_with('/') && v.ends_with('/') {
-                    let re = regex::Regex::new(v).unwrap();
-                    re.is_match(&s.1)
-                } else {
-                    s.1 == *v
-                }
-            } else {
-                true
+    /// The matched tokens' text, with a space wherever the source separated them
+    pub fn matched(&self) -> String {
+        let mut out = String::new();
+        for (i, v) in self.window.iter().enumerate() {
+            let gap = i > 0 && {
+                let (end, start) = (self.window[i - 1].span().end(), v.span().start());
+                end.line != start.line || end.column != start.column
+            };
+
+            if gap {
+                out.push(' ');
+            }
+            if v.kind() == "Lifetime" {
+                out.push('\'');
             }
+            out.push_str(v.value());
         }
+
+        out
     }
-}
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct Rule {
-    pub name: String,
-    pub description: String,
-    pub help: Option<String>,
-    #[serde(deserialize_with = "deser_range_from_array")]
-    pub range: RangeInclusive<usize>,
-    pub pattern: Vec<Needle>,
-    pub link: Option<String>,
-    #[serde(default)]
-    pub fails: bool,
-    pub replace: Option<String>,
-}
+    /// Fills `{match}` with the matched text, and `{0}`, `{1}`, ... with the matched tokens
+    pub fn interpolate(&self, s: &str) -> String {
+        let mut out = s.replace("{match}", &self.matched());
+        for (i, v) in self.window.iter().enumerate() {
+            out = out.replace(&format!("{{{i}}}"), v.value());
+        }
 
-impl Rule {
-    pub fn test(&self, s: &Vec<Named>) -> Result<(), Vec<Named>> {
-        for (m, _) in s
-            .iter()
-            .enumerate()
-            .filter(|(_, v)| self.pattern.first().unwrap().0 == v.0)
-        {
-            let window = s.iter().skip(m).take(self.pattern.len());
-            if window.len() != self.pattern.len() {
-                continue;
-            }
+        out
+    }
 
-            if window
-                .clone()
-                .zip(self.pattern.iter())
-                .all(|(a, b)| b.test(a))
-            {
-                return Err(window.cloned().collect());
-            }
-        }
+    /// The rule's description, with placeholders filled
+    pub fn description(&self) -> String {
+        self.interpolate(&self.rule.description)
+    }
 
-        Ok(())
+    /// The rule's link, with placeholders filled, e.g. for docs pages per identifier
+    pub fn link(&self) -> Option<String> {
+        self.rule.link.as_deref().map(|l| self.interpolate(l))
     }
-}
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct Rules {
-    pub rules: HashMap<String, Rule>,
-}
+    /// The rule's short label for its first suggestion, with placeholders filled
+    pub fn fix_title(&self) -> Option<String> {
+        self.rule.fix_title.as_deref().map(|t| self.interpolate(t))
+    }
 
-#[derive(Debug, Clone)]
-pub struct LintError {
-    pub rule: Rule,
-    pub fails: bool,
-    pub line: (String, usize),
-    pub window: Vec<Named>,
-    pub source: NamedSource<String>,
-}
+    /// The rule's suggestions, with `${name}` filled from the match's captures
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        let captures = self.rule.captures(&self.window);
+        let mut suggestions = self.rule.suggestions();
+        for s in suggestions.iter_mut() {
+            for (k, v) in captures.iter() {
+                s.replace = s.replace.replace(&format!("${{{k}}}"), v);
+            }
+        }
+
+        suggestions
+    }
 
-impl LintError {
     pub fn help(&self) -> Option<String> {
-        let h = self.rule.help.clone();
-        let r = match self.rule.replace.clone() {
-            Some(r) => format!(
-                "Try replacing '{}' with '{r}'",
-                self.window
-                    .iter()
-                    .map(|v| v.clone().1)
-                    .collect::<Vec<_>>()
-                    .join("")
-            ),
-            None => String::new(),
-        };
+        let h = self.rule.help.as_deref().map(|h| self.interpolate(h));
+        let matched = self.matched();
+        let r = self
+            .suggestions()
+            .iter()
+            .map(|s| format!("Try replacing '{matched}' with '{}'", s.replace))
+            .collect::<Vec<_>>()
+            .join("\n");
 
         if let Some(h) = h {
-            Some(format!("{}\n{}", h, r))
+            match r.is_empty() {
+                true => Some(h),
+                false => Some(format!("{}\n{}", h, r)),
+            }
         } else if !r.is_empty() {
             Some(r)
         } else {
@@ -184,7 +1584,12 @@ impl LintError {
 
 impl Display for LintError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{}: {}", self.rule.name, self.rule.description))
+        f.write_str(&format!("{}: {}", self.rule.name, self.description()))?;
+        if self.truncated {
+            f.write_str(" (truncated)")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -210,8 +1615,8 @@ impl Diagnostic for LintError {
     }
 
     fn url<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
-        if let Some(v) = &self.rule.link {
-            Some(Box::new(v.clone()))
+        if let Some(v) = self.link() {
+            Some(Box::new(v))
         } else {
             None
         }
@@ -225,18 +1630,20 @@ impl Diagnostic for LintError {
         Some(Box::new(
             [LabeledSpan::new_primary_with_span(
                 None,
-                span(
-                    self.source.inner().to_string(),
-                    self.window.clone(),
-                    self.rule.range.clone(),
-                ),
+                span(&self.position),
             )]
             .into_iter(),
         ))
     }
 
     fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
-        None
+        if self.note.is_none() && self.first_copy.is_none() {
+            return None;
+        }
+
+        let note = self.note.iter().map(|n| n as &dyn Diagnostic);
+        let first = self.first_copy.iter().map(|c| c as &dyn Diagnostic);
+        Some(Box::new(note.chain(first)))
     }
 
     fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
@@ -244,20 +1651,97 @@ impl Diagnostic for LintError {
     }
 }
 
-pub fn span(c: String, s: Vec<Named>, r: RangeInclusive<usize>) -> SourceSpan {
-    let s = s[r.clone()].iter().map(|v| v.span()).collect::<Vec<_>>();
-    let f = s.first().unwrap();
-    let lc = f.start();
-    let length = s
-        .clone()
-        .iter()
-        .map(|v| v.byte_range().end - v.byte_range().start)
-        .sum::<usize>();
+/// The source span miette highlights for a position
+pub fn span(p: &Position) -> SourceSpan {
+    SourceSpan::new(p.byte_start.into(), p.byte_end - p.byte_start)
+}
+
+/// Names the kind of a JSON value, for error messages
+fn json_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// Replaces `${VAR}` with the variable from the process environment, and `$$` with `$`
+fn expand_env(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unclosed `${{` in `{s}`"))?;
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("environment variable `{name}` isn't set"))?;
+            out.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// A map of rules keyed by name, or a list of rules, as read by [`deser_rules`]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum RuleSet {
+    Map(IndexMap<String, Rule>),
+    List(Vec<Rule>),
+}
+
+/// Reads rules either as a map keyed by name, or a list of rules keyed by their `name` field,
+/// rejecting rules that can't be matched and expanding environment variables in their links and help
+pub fn deser_rules<'de, D>(deserializer: D) -> Result<IndexMap<String, Rule>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct RulesVisitor;
+    impl<'de> de::Visitor<'de> for RulesVisitor {
+        type Value = IndexMap<String, Rule>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map of rules, or a list of rules")
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            IndexMap::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            let rules = Vec::<Rule>::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+            Ok(rules.into_iter().map(|r| (r.name.clone(), r)).collect())
+        }
+    }
 
-    SourceSpan::new(
-        SourceOffset::from_location(c, lc.line, lc.column + 1),
-        length,
-    )
+    let mut rules = deserializer.deserialize_any(RulesVisitor)?;
+    let mut problems = Vec::new();
+    for (name, rule) in rules.iter_mut() {
+        let found = rule.problems().into_iter().chain(rule.expand_env());
+        problems.extend(found.map(|e| format!("Rule '{name}': {e}")));
+    }
+
+    match problems.as_slice() {
+        [] => Ok(rules),
+        problems => Err(de::Error::custom(problems.join("\n"))),
+    }
 }
 
 pub fn deser_range_from_array<'de, D>(deserializer: D) -> Result<RangeInclusive<usize>, D::Error>
@@ -271,3 +1755,831 @@ where
     }
     Ok(v[0]..=v[1])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> Rules {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn tags_drop_untagged_rules() {
+        let mut r = rules(
+            r#"
+            [rules.style]
+            name = "style"
+            description = "d"
+            tags = ["style"]
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [rules.perf]
+            name = "perf"
+            description = "d"
+            tags = ["perf"]
+            range = [0, 0]
+            pattern = [["Ident", "b"]]
+
+            [rules.untagged]
+            name = "untagged"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "c"]]
+            "#,
+        );
+
+        let mut all = r.clone();
+        all.retain_tags(&[]);
+        assert_eq!(all.rules.len(), 3);
+
+        r.retain_tags(&["style".to_string()]);
+        assert_eq!(r.rules.keys().collect::<Vec<_>>(), ["style"]);
+    }
+
+    #[test]
+    fn span_lines_constrains_match_height() {
+        let lines = |span_lines: &str| {
+            let r = rules(&format!(
+                r#"
+                [rules.call]
+                name = "call"
+                description = "d"
+                span_lines = {span_lines}
+                range = [0, 2]
+                pattern = [["Ident", "f"], ["Delim", "("], ["Ident", "x"]]
+                "#
+            ));
+            crate::lint_str("fn a() { f(x); f(\n    x); }", "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window.last().unwrap().span().end().line)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines("\"single\""), [1]);
+        assert_eq!(lines("\"multi\""), [2]);
+        assert_eq!(lines("[2, 3]"), [2]);
+        assert_eq!(lines("[1, 1]"), [1]);
+    }
+
+    const OVERRIDDEN: &str = r#"
+        [rules.style]
+        name = "style"
+        description = "everywhere"
+        tags = ["style"]
+        range = [0, 0]
+        pattern = [["Ident", "a"]]
+
+        [[overrides]]
+        paths = ["tests/**"]
+
+        [overrides.rules.style]
+        name = "style"
+        description = "in tests"
+        tags = ["style"]
+        range = [0, 0]
+        pattern = [["Ident", "a"]]
+
+        [overrides.rules.perf]
+        name = "perf"
+        description = "d"
+        tags = ["perf"]
+        range = [0, 0]
+        pattern = [["Ident", "b"]]
+        "#;
+
+    fn names(r: &Rules) -> Vec<&str> {
+        let mut names = r.rules.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn overrides_apply_to_matching_files() {
+        let r = rules(OVERRIDDEN);
+
+        let src = r.for_file("src/lib.rs");
+        assert_eq!(names(&src), ["style"]);
+        assert_eq!(src.rules["style"].description, "everywhere");
+
+        let tests = r.for_file("tests/a/cli.rs");
+        assert_eq!(names(&tests), ["perf", "style"]);
+        assert_eq!(tests.rules["style"].description, "in tests");
+    }
+
+    #[test]
+    fn tags_filter_override_rules() {
+        let mut r = rules(OVERRIDDEN);
+        r.retain_tags(&["style".to_string()]);
+        assert_eq!(names(&r.for_file("tests/a.rs")), ["style"]);
+    }
+
+    #[test]
+    fn overrides_change_whether_rules_fail() {
+        let r = rules(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            fails = true
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [[overrides]]
+            paths = ["examples/**"]
+            fails = { u = false }
+
+            [[overrides]]
+            paths = ["src/**"]
+            fails = { missing = true }
+            "#,
+        );
+
+        assert!(r.for_file("src/lib.rs").rules["u"].fails);
+        assert!(!r.for_file("examples/a.rs").rules["u"].fails);
+        assert_eq!(
+            r.unknown_overridden(),
+            [r#"Override for ["src/**"]: no rule named 'missing'"#]
+        );
+    }
+
+    #[test]
+    fn keyword_needles() {
+        let matched = |needle: &str| {
+            let r: Rules = serde_json::from_str(&format!(
+                r#"{{ "rules": {{ "k": {{
+                    "name": "k", "description": "d", "range": [0, 0], "pattern": [{needle}]
+                }} }} }}"#
+            ))
+            .unwrap();
+            crate::lint_str("fn f() { match foo { _ => if bar {} } }", "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window[0].1.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched(r#"["Keyword", null]"#), ["fn", "match", "if"]);
+        assert_eq!(matched(r#"["Keyword", "if"]"#), ["if"]);
+        assert!(matched(r#"["Keyword", "foo"]"#).is_empty());
+    }
+
+    #[test]
+    fn named_exposes_kind_and_value() {
+        let named = Named::new("Ident", "foo".to_string(), Span::call_site());
+        assert_eq!(named.kind(), "Ident");
+        assert_eq!(named.value(), "foo");
+    }
+
+    #[test]
+    fn backreferences_match_captured_text() {
+        let r = rules(
+            r#"
+            [rules.twice]
+            name = "twice"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "$1"], ["Ident", "\\1"]]
+            "#,
+        );
+        let count = |source: &str| crate::lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { foo foo }"), 1);
+        assert_eq!(count("fn f() { foo bar }"), 0);
+    }
+
+    #[test]
+    fn names_rules_with_unknown_fields() {
+        let value = serde_json::json!({ "rules": { "u": {
+            "name": "u", "descripton": "d", "range": [0, 0], "pattern": [["Ident", "u"]]
+        } } });
+
+        assert!(serde_json::from_value::<Rules>(value.clone()).is_err());
+        let invalid = Rules::find_invalid_rule(&value).unwrap();
+        assert!(
+            invalid.starts_with("Rule 'u': unknown field `descripton`"),
+            "{invalid}"
+        );
+    }
+
+    #[test]
+    fn rule_lists_match_rule_maps() {
+        let listed = rules(
+            r#"
+            [[rules]]
+            name = "a"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [[rules]]
+            name = "b"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "b"], ["Punct", "!"]]
+            "#,
+        );
+        let mapped = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [rules.b]
+            name = "b"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "b"], ["Punct", "!"]]
+            "#,
+        );
+
+        assert_eq!(
+            serde_json::to_value(&listed).unwrap(),
+            serde_json::to_value(&mapped).unwrap()
+        );
+    }
+
+    #[test]
+    fn unescapes_literals() {
+        assert_eq!(unescape_literal(r#""a\n\x41\u{1F600}""#).unwrap(), "a\nA😀");
+        assert_eq!(unescape_literal(r##"r#"a\n"#"##).unwrap(), r"a\n");
+        assert_eq!(unescape_literal(r"b'\''").unwrap(), "'");
+        assert_eq!(unescape_literal("12"), None);
+    }
+
+    #[test]
+    fn str_needles_match_literal_contents() {
+        let matched = |value: &str| {
+            let r: Rules = serde_json::from_value(serde_json::json!({ "rules": { "s": {
+                "name": "s", "description": "d", "range": [0, 0], "pattern": [["Str", value]]
+            } } }))
+            .unwrap();
+            crate::lint_str(r#"fn f() { a("hi"); b("say \"hi\""); c(12); }"#, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window[0].1.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched("hi"), [r#""hi""#]);
+        assert_eq!(matched(r#"say "hi""#), [r#""say \"hi\"""#]);
+        assert!(matched("12").is_empty());
+    }
+
+    #[test]
+    fn stmt_end_needles_match_statement_boundaries() {
+        let r: Rules = serde_json::from_value(serde_json::json!({ "rules": { "call": {
+            "name": "call", "description": "d", "range": [0, 2],
+            "pattern": [["Delim", "("], ["Delim", ")"], ["StmtEnd", null]]
+        } } }))
+        .unwrap();
+        let count = |source: &str| crate::lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { foo(); }"), 1);
+        assert_eq!(count("fn f() { { foo() } }"), 1);
+        assert_eq!(count("fn f() { foo().bar; }"), 0);
+    }
+
+    #[test]
+    fn interpolates_matched_text() {
+        let r = rules(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "Found {match}"
+            help = "replace {1} with expect"
+            range = [0, 3]
+            pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+            "#,
+        );
+        let lint = &crate::lint_str("fn f() { a.unwrap(); }", "a.rs", &r).unwrap()[0];
+
+        assert_eq!(lint.to_string(), "u: Found .unwrap()");
+        assert_eq!(lint.help().unwrap(), "replace unwrap with expect");
+    }
+
+    #[test]
+    fn rejects_ranges_outside_the_pattern() {
+        let parsed = toml::from_str::<Rules>(
+            r#"
+            [rules.wide]
+            name = "wide"
+            description = "d"
+            range = [0, 5]
+            pattern = [["Ident", "a"], ["Ident", "b"]]
+            "#,
+        );
+        let error = parsed.unwrap_err().to_string();
+        assert!(
+            error.contains("Rule 'wide': range [0, 5] is out of bounds for a 2 token pattern"),
+            "{error}"
+        );
+
+        let overridden = serde_json::from_value::<Rules>(serde_json::json!({
+            "rules": {},
+            "overrides": [{ "paths": ["*.rs"], "rules": [{
+                "name": "empty", "description": "d", "range": [0, 0], "pattern": []
+            }] }]
+        }));
+        assert!(overridden
+            .unwrap_err()
+            .to_string()
+            .contains("Rule 'empty': pattern is empty"));
+    }
+
+    #[test]
+    fn unraw_needles_match_raw_identifiers_by_name() {
+        let matched = |needle: &str| {
+            let r = rules(&format!(
+                r#"
+                [rules.t]
+                name = "t"
+                description = "d"
+                range = [0, 0]
+                pattern = [{needle}]
+                "#
+            ));
+            crate::lint_str("fn r#type() { type_of(r#match); }", "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window[0].1.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched(r#"["Unraw", "type"]"#), ["r#type"]);
+        assert_eq!(matched(r#"["Unraw", "match"]"#), ["r#match"]);
+        // Plain `Ident` needles still only match the raw form as written
+        assert!(matched(r#"["Ident", "type"]"#).is_empty());
+        assert_eq!(matched(r##"["Ident", "r#type"]"##), ["r#type"]);
+    }
+
+    #[test]
+    fn matches_finds_every_non_overlapping_window() {
+        let r = rules(
+            r#"
+            [rules.call]
+            name = "call"
+            description = "d"
+            range = [0, 2]
+            pattern = [["Ident", "/^[a-z]$/"], ["Delim", "("], ["Delim", ")"]]
+
+            [rules.pair]
+            name = "pair"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Punct", ":"], ["Punct", ":"]]
+
+            [rules.none]
+            name = "none"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "missing"]]
+            "#,
+        );
+        let source = "fn f() { a(); b::c::d(); e(x); }";
+        let tokens = crate::parse(source.parse().unwrap(), &r.options);
+        let windows = |name: &str| {
+            r.rules[name]
+                .matches(&tokens)
+                .iter()
+                .map(|w| w.iter().map(|t| t.value()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(windows("call"), ["f()", "a()", "d()"]);
+        assert_eq!(windows("pair"), ["::", "::"]);
+        assert!(windows("none").is_empty());
+    }
+
+    #[test]
+    fn finds_shadowed_rules() {
+        let r = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+
+            [rules.b]
+            name = "b"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "foo"]]
+
+            [rules.c]
+            name = "c"
+            description = "d"
+            range = [0, 1]
+            pattern = [["Ident", "foo"], ["Punct", "!"]]
+
+            [rules.d]
+            name = "d"
+            description = "d"
+            range = [0, 0]
+            pattern = [["Ident", "bar"]]
+            "#,
+        );
+
+        assert_eq!(
+            r.shadowed(),
+            [
+                "Rules 'a' and 'b' have identical patterns",
+                "Rule 'a' matches everywhere 'c' does",
+                "Rule 'b' matches everywhere 'c' does",
+            ]
+        );
+        assert!(rules(OVERRIDDEN).shadowed().is_empty());
+    }
+
+    #[test]
+    fn max_items_fires_on_large_groups() {
+        let r = rules(
+            r#"
+            [rules.args]
+            name = "args"
+            description = "d"
+            range = [0, 1]
+            max_items = 4
+            pattern = [["Ident", "call"], ["Delim", "("]]
+            "#,
+        );
+        let count = |source: &str| crate::lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { call(a, b, (c, d), e, f); }"), 1);
+        assert_eq!(count("fn f() { call(a, b); }"), 0);
+        assert_eq!(count("fn f() { call(a, b, c, d,); }"), 0);
+    }
+
+    #[test]
+    fn positions_locate_single_and_multi_line_highlights() {
+        let r = rules(
+            r#"
+            [rules.u]
+            name = "u"
+            description = "d"
+            range = [1, 2]
+            pattern = [["Ident", "x"], ["Punct", "."], ["Ident", "unwrap"]]
+            "#,
+        );
+        let position = |source: &str| {
+            crate::lint_str(source, "a.rs", &r).unwrap()[0]
+                .position
+                .clone()
+        };
+
+        assert_eq!(
+            position("fn f() {\n    let é = x.unwrap();\n}"),
+            Position {
+                start_line: 2,
+                start_col: 14,
+                end_line: 2,
+                end_col: 21,
+                byte_start: 23,
+                byte_end: 30,
+                line_text: "    let é = x.unwrap();".to_string(),
+                line_byte_start: 9,
+            }
+        );
+        assert_eq!(
+            position("fn f() {\n    x.\n        unwrap();\n}"),
+            Position {
+                start_line: 2,
+                start_col: 6,
+                end_line: 3,
+                end_col: 15,
+                byte_start: 14,
+                byte_end: 30,
+                line_text: "    x.".to_string(),
+                line_byte_start: 9,
+            }
+        );
+    }
+
+    #[test]
+    fn describes_every_problem_with_a_rule() {
+        let rule: Rule = serde_json::from_value(serde_json::json!({
+            "name": "r", "description": "d", "range": [0, 3],
+            "pattern": [["Ident", "/(/"], ["Ident", "/^ok$/"]]
+        }))
+        .unwrap();
+        let problems = rule.problems();
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            problems[0],
+            "range [0, 3] is out of bounds for a 2 token pattern"
+        );
+        assert!(problems[1].starts_with("needle 0 has an invalid regex"));
+        assert!(rules(OVERRIDDEN).rules["style"].problems().is_empty());
+    }
+
+    #[test]
+    fn object_needles_match_tuple_needles() {
+        let needles = |v: serde_json::Value| serde_json::from_value::<Vec<Needle>>(v).unwrap();
+
+        assert_eq!(
+            needles(serde_json::json!([
+                { "kind": "Ident", "value": "foo" },
+                { "kind": "Literal", "value": "^\\d+$", "regex": true },
+                { "kind": "Delim" },
+            ])),
+            needles(serde_json::json!([
+                ["Ident", "foo"],
+                ["Literal", "/^\\d+$/"],
+                ["Delim", null],
+            ]))
+        );
+        assert!(serde_json::from_value::<Needle>(
+            serde_json::json!({ "kind": "Ident", "regx": true })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn expands_environment_variables_in_links() {
+        std::env::set_var("SPLINT_TEST_DOCS_BASE", "https://docs.example.com/v2");
+        let rule = |link: &str| {
+            serde_json::from_value::<Rules>(serde_json::json!({ "rules": { "r": {
+                "name": "r", "description": "d", "range": [0, 0],
+                "link": link, "help": "costs $$5",
+                "pattern": [["Ident", "a"]]
+            } } }))
+            .map(|r| r.rules["r"].clone())
+            .map_err(|e| e.to_string())
+        };
+
+        let r = rule("${SPLINT_TEST_DOCS_BASE}/unwrap.html").unwrap();
+        assert_eq!(
+            r.link.as_deref(),
+            Some("https://docs.example.com/v2/unwrap.html")
+        );
+        assert_eq!(r.help.as_deref(), Some("costs $5"));
+        assert_eq!(
+            rule("${SPLINT_TEST_MISSING}/a").unwrap_err(),
+            "Rule 'r': environment variable `SPLINT_TEST_MISSING` isn't set"
+        );
+    }
+
+    #[test]
+    fn adjacent_needles_must_touch() {
+        let r = rules(
+            r#"
+            [rules.call]
+            name = "call"
+            description = "d"
+            range = [0, 1]
+            adjacent = [1]
+            pattern = [["Ident", "foo"], ["Delim", "("]]
+            "#,
+        );
+        let count = |source: &str| crate::lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { foo(); }"), 1);
+        assert_eq!(count("fn f() { foo (); }"), 0);
+        assert_eq!(count("fn f() { foo\n(); }"), 0);
+
+        let mut wide = r.rules["call"].clone();
+        wide.adjacent = vec![2];
+        assert_eq!(
+            wide.problems(),
+            ["adjacent needle 2 has no previous needle in a 2 token pattern"]
+        );
+    }
+
+    #[test]
+    fn regex_flags_change_matching() {
+        let r: Rules = serde_json::from_value(serde_json::json!({ "rules": {
+            "todo": {
+                "name": "todo", "description": "d", "range": [0, 0],
+                "pattern": [{ "kind": "Ident", "value": "^todo$", "regex": true, "flags": "i" }]
+            },
+            "line": {
+                "name": "line", "description": "d", "range": [0, 0],
+                "pattern": [{ "kind": "Str", "value": "^fixme$", "regex": true, "flags": "m" }]
+            }
+        } }))
+        .unwrap();
+        let names = |source: &str| {
+            let mut names = crate::lint_str(source, "a.rs", &r)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.rule.name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names("fn f() { TODO; Todo; }"), ["todo", "todo"]);
+        assert_eq!(names("fn f() { \"a\\nfixme\\nb\"; }"), ["line"]);
+        assert!(names("fn f() { \"a fixme b\"; }").is_empty());
+
+        let bad: Rule = serde_json::from_value(serde_json::json!({
+            "name": "r", "description": "d", "range": [0, 0],
+            "pattern": [{ "kind": "Ident", "value": "a", "flags": "q" }]
+        }))
+        .unwrap();
+        assert_eq!(bad.problems(), ["needle 0 has an unknown regex flag `q`"]);
+    }
+
+    #[test]
+    fn explains_documents_that_arent_rules() {
+        let shape = |v: serde_json::Value| Rules::find_invalid_shape(&v);
+
+        assert_eq!(
+            shape(serde_json::json!([{ "name": "a" }])).unwrap(),
+            "expected an object with a `rules` key, found an array"
+        );
+        assert_eq!(
+            shape(serde_json::json!({ "name": "a", "version": 1 })).unwrap(),
+            "missing a `rules` key, found `name`, `version`"
+        );
+        assert_eq!(
+            shape(serde_json::json!({ "rules": "a" })).unwrap(),
+            "`rules` should be a map or list of rules, found a string"
+        );
+        assert_eq!(shape(serde_json::json!({ "rules": [] })), None);
+    }
+
+    #[test]
+    fn not_followed_by_peeks_without_consuming() {
+        let r = rules(
+            r#"
+            [rules.bare]
+            name = "bare"
+            description = "d"
+            range = [0, 0]
+            not_followed_by = ["Punct", "<"]
+            pattern = [["Ident", "Box"]]
+            "#,
+        );
+        let matched = |source: &str| {
+            crate::lint_str(source, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(LintError::matched)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matched("type A = Box<T>;").is_empty());
+        assert_eq!(matched("fn f() { g(Box) }"), ["Box"]);
+        assert_eq!(matched("fn f() { Box }"), ["Box"]);
+    }
+
+    #[test]
+    fn reads_literal_numbers() {
+        assert_eq!(literal_number("1_000u32"), Some(1000.0));
+        assert_eq!(literal_number("0xffu8"), Some(255.0));
+        assert_eq!(literal_number("2.5f64"), Some(2.5));
+        assert_eq!(literal_number("\"12\""), None);
+    }
+
+    #[test]
+    fn number_needles_compare_values() {
+        let matched = |value: &str| {
+            let r: Rules = serde_json::from_value(serde_json::json!({ "rules": { "n": {
+                "name": "n", "description": "d", "range": [0, 0], "pattern": [["Number", value]]
+            } } }))
+            .unwrap();
+            crate::lint_str(r#"fn f() { a(300); b(10); c("300"); }"#, "a.rs", &r)
+                .unwrap()
+                .iter()
+                .map(|e| e.window[0].1.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched(">255"), ["300"]);
+        assert_eq!(matched("<=10"), ["10"]);
+        assert_eq!(matched("1..=300"), ["300", "10"]);
+        assert!(matched("1..10").is_empty());
+    }
+
+    #[test]
+    fn kind_sets_match_any_listed_kind() {
+        let r: Rules = serde_json::from_value(serde_json::json!({ "rules": { "k": {
+            "name": "k", "description": "d", "range": [0, 0],
+            "pattern": [[["Ident", "Literal"], null], ["Punct", ";"]]
+        } } }))
+        .unwrap();
+        let matched = crate::lint_str("fn f() { a; 1; *; }", "a.rs", &r)
+            .unwrap()
+            .iter()
+            .map(|e| e.window[0].1.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(matched, ["a", "1"]);
+    }
+
+    #[test]
+    fn rules_need_a_pattern_or_repeated_tokens() {
+        let problem = |fields: &str| {
+            toml::from_str::<Rules>(&format!(
+                "[rules.r]\nname = \"r\"\ndescription = \"d\"\nrange = [0, 0]\n{fields}"
+            ))
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert!(problem("").contains("Rule 'r': pattern is empty"));
+        assert!(problem("repeated_tokens = 0").contains("repeated_tokens must be at least 1"));
+        assert!(
+            problem("repeated_tokens = 2\npattern = [[\"Ident\", \"a\"]]")
+                .contains("has both a pattern and repeated_tokens")
+        );
+    }
+
+    #[test]
+    fn repeated_tokens_point_at_the_first_copy() {
+        let r = rules(
+            r#"
+            [rules.copy]
+            name = "copy"
+            description = "d"
+            range = [0, 5]
+            repeated_tokens = 6
+            "#,
+        );
+        let source = "fn f() {\n    a.b(c);\n    x;\n    a.b(c);\n    a.b(d);\n}";
+        let e = crate::lint_str(source, "a.rs", &r).unwrap();
+
+        assert_eq!(e.len(), 1);
+        assert_eq!(e[0].position.start_line, 4);
+        assert_eq!(e[0].first_copy.as_ref().unwrap().position.start_line, 2);
+    }
+
+    #[test]
+    fn group_needles_match_delimiters_by_name() {
+        let r = rules(
+            r#"
+            [rules.braced]
+            name = "braced"
+            description = "d"
+            range = [0, 2]
+            pattern = [["Ident", "foo"], ["Punct", "!"], ["Group", "Brace"]]
+            "#,
+        );
+        let count = |source: &str| crate::lint_str(source, "a.rs", &r).unwrap().len();
+
+        assert_eq!(count("fn f() { foo! { a } }"), 1);
+        assert_eq!(count("fn f() { foo!(a); foo![a]; bar! { a } }"), 0);
+    }
+
+    #[test]
+    fn star_stays_within_a_directory() {
+        let g = glob("*_generated.rs").unwrap();
+        assert!(g.is_match("a_generated.rs"));
+        assert!(!g.is_match("src/a_generated.rs"));
+        assert!(glob("**/*_generated.rs")
+            .unwrap()
+            .is_match("src/a/a_generated.rs"));
+        assert!(glob("src/{a,b}.rs").unwrap().is_match("src/b.rs"));
+        assert!(!glob("src/*.rs").unwrap().is_match("src/a/b.rs"));
+    }
+
+    #[test]
+    fn overrides_and_applies_to_share_glob_rules() {
+        let r = rules(
+            r#"
+            [rules.a]
+            name = "a"
+            description = "d"
+            applies_to = ["src/*.rs", "build.rs"]
+            range = [0, 0]
+            pattern = [["Ident", "a"]]
+
+            [[overrides]]
+            paths = ["tests/*.rs"]
+            "#,
+        );
+        let rule = &r.rules["a"];
+        assert!(rule.applies_to("src/lib.rs"));
+        assert!(!rule.applies_to("src/a/lib.rs"));
+        assert!(rule.applies_to("crates/x/build.rs"));
+
+        assert!(r.overrides[0].matches("tests/cli.rs"));
+        assert!(!r.overrides[0].matches("tests/ui/cli.rs"));
+    }
+
+    #[test]
+    fn links_fill_match_placeholders() {
+        let r = rules(
+            r#"
+            [rules.call]
+            name = "call"
+            description = "d"
+            link = "https://docs.example.com/{0}.html#{match}"
+            range = [0, 0]
+            pattern = [["Ident", "/^[a-z]+$/"], ["Delim", "("]]
+            "#,
+        );
+        let e = &crate::lint_str("let a = sleep(1);", "a.rs", &r).unwrap()[0];
+        let url = "https://docs.example.com/sleep.html#sleep(";
+
+        assert_eq!(e.url().unwrap().to_string(), url);
+        assert_eq!(e.to_serializable().link.as_deref(), Some(url));
+    }
+}
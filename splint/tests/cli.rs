@@ -0,0 +1,1212 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// A scratch directory holding the given files
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("splint-cli-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (path, content) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+fn splint(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_splint"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn splint_stdin(dir: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_splint"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(o: &Output) -> String {
+    String::from_utf8_lossy(&o.stderr).to_string()
+}
+
+const A: &str = r#"
+[rules.shared]
+name = "shared"
+description = "shared from a"
+range = [0, 0]
+pattern = [["Ident", "foo"]]
+
+[rules.only_a]
+name = "only_a"
+description = "only in a"
+range = [0, 0]
+pattern = [["Ident", "bar"]]
+"#;
+
+const B: &str = r#"
+[rules.shared]
+name = "shared"
+description = "shared from b"
+range = [0, 0]
+pattern = [["Ident", "baz"]]
+
+[rules.only_b]
+name = "only_b"
+description = "only in b"
+range = [0, 0]
+pattern = [["Ident", "qux"]]
+"#;
+
+#[test]
+fn later_rules_files_override_earlier_ones() {
+    let dir = project(
+        "merge",
+        &[
+            ("a.toml", A),
+            ("b.toml", B),
+            ("a.rs", "fn f() { foo; bar; baz; qux; }"),
+        ],
+    );
+
+    for args in [
+        ["-r", "a.toml", "-r", "b.toml"].as_slice(),
+        &["-r", "a.toml,b.toml"],
+    ] {
+        let o = splint(&dir, &[args, &["a.rs"]].concat());
+        let err = stderr(&o);
+        assert!(err.contains("shared from b"), "{err}");
+        assert!(!err.contains("shared from a"), "{err}");
+        assert!(err.contains("only in a"), "{err}");
+        assert!(err.contains("only in b"), "{err}");
+    }
+}
+
+#[test]
+fn github_annotations() {
+    let rules = r#"
+[rules.u]
+name = "Disallow Unwrap"
+description = "No unwrap"
+fails = true
+range = [0, 1]
+pattern = [["Punct", "."], ["Ident", "unwrap"]]
+"#;
+    let dir = project(
+        "github",
+        &[("rules.toml", rules), ("a.rs", "fn a() { x.unwrap(); }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--format", "github", "a.rs"]);
+    assert_eq!(
+        String::from_utf8_lossy(&o.stdout).trim(),
+        "::error file=a.rs,line=1,col=11,endLine=1,endColumn=18,title=Disallow Unwrap::Disallow Unwrap: No unwrap"
+    );
+}
+
+#[test]
+fn max_per_rule_truncates() {
+    let dir = project(
+        "max",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; bar; bar; }")],
+    );
+
+    let err = stderr(&splint(
+        &dir,
+        &["-r", "rules.toml", "--max-per-rule", "2", "a.rs"],
+    ));
+    assert_eq!(err.matches("only in a").count(), 2, "{err}");
+    assert_eq!(err.matches("(truncated)").count(), 1, "{err}");
+
+    let err = stderr(&splint(&dir, &["-r", "rules.toml", "a.rs"]));
+    assert_eq!(err.matches("only in a").count(), 3, "{err}");
+    assert!(!err.contains("(truncated)"));
+}
+
+#[test]
+fn json_lines() {
+    let dir = project(
+        "jsonl",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() {\n    bar;\n    bar;\n}"),
+        ],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--format", "jsonl", "a.rs"]);
+    let lines = String::from_utf8_lossy(&o.stdout)
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["rule"], "only_a");
+    assert_eq!(lines[0]["severity"], "warning");
+    assert_eq!(lines[0]["file"], "a.rs");
+    assert_eq!(
+        (&lines[0]["start_line"], &lines[0]["start_col"]),
+        (&2.into(), &5.into())
+    );
+    assert_eq!(lines[1]["start_line"], 3);
+    assert_eq!(lines[0]["message"], "only_a: only in a");
+}
+
+#[test]
+fn analyze_mode_only_prints_splint_diagnostics() {
+    let dir = project(
+        "analyze",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; bar; }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "-a", "a.rs"]);
+    assert_eq!(o.status.code(), Some(0));
+    let lines = String::from_utf8_lossy(&o.stdout)
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|l| l["reason"] == "compiler-message"));
+    assert!(!stderr(&o).contains("Cargo.toml"), "{}", stderr(&o));
+}
+
+#[test]
+fn stdin_filename_names_piped_input() {
+    let dir = project("stdin", &[("rules.toml", A)]);
+    let file = |args: &[&str]| {
+        let o = splint_stdin(
+            &dir,
+            &[&["-r", "rules.toml", "--format", "jsonl"], args, &["-"]].concat(),
+            "fn f() { bar; }",
+        );
+        let lint: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+        lint["file"].as_str().unwrap().to_string()
+    };
+
+    assert_eq!(file(&["--stdin-filename", "src/piped.rs"]), "src/piped.rs");
+    assert_eq!(file(&[]), "<stdin>");
+}
+
+#[test]
+fn summary_counts_fails_and_warnings_across_files() {
+    let rules = r#"
+[rules.fail]
+name = "fail"
+description = "d"
+fails = true
+range = [0, 0]
+pattern = [["Ident", "bad"]]
+
+[rules.warn]
+name = "warn"
+description = "d"
+range = [0, 0]
+pattern = [["Ident", "meh"]]
+"#;
+    let dir = project(
+        "summary",
+        &[
+            ("rules.toml", rules),
+            ("a.rs", "fn f() { bad; meh; meh; }"),
+            ("b.rs", "fn g() { bad; meh; }"),
+            ("c.rs", "fn h() {}"),
+        ],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "a.rs", "b.rs", "c.rs"]);
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert!(out.contains("2 fails"), "{out}");
+    assert!(out.contains("3 warnings"), "{out}");
+    assert!(out.contains("Finished linting 3 files"), "{out}");
+    assert_eq!(o.status.code(), Some(1));
+}
+
+#[test]
+fn watch_relints_changed_files() {
+    let dir = project(
+        "watch",
+        &[("rules.toml", A), ("src/a.rs", "fn f() { foo; }")],
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_splint"))
+        .current_dir(&dir)
+        .args(["-r", "rules.toml", "--watch", "src"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (tx, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = tx.send(line);
+        }
+    });
+    let wait_for = |summary: &str| loop {
+        match lines.recv_timeout(Duration::from_secs(10)) {
+            Ok(line) if line.contains(summary) => break true,
+            Ok(_) => continue,
+            Err(_) => break false,
+        }
+    };
+
+    let first = wait_for("1 warnings");
+    if first {
+        fs::write(dir.join("src/a.rs"), "fn f() { foo; bar; }").unwrap();
+    }
+    let relinted = first && wait_for("2 warnings");
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(first, "no summary for the initial lint");
+    assert!(relinted, "no summary after modifying a watched file");
+}
+
+#[test]
+fn misspelled_fields_name_the_rule() {
+    let rules = r#"
+[rules.u]
+name = "u"
+descripton = "d"
+range = [0, 0]
+pattern = [["Ident", "u"]]
+"#;
+    let dir = project(
+        "misspelled",
+        &[("rules.toml", rules), ("a.rs", "fn f() {}")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "a.rs"]);
+    let err = stderr(&o);
+    assert!(err.contains("Rule 'u': unknown field"), "{err}");
+    assert!(err.contains("`descripton`"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn count_format_and_exit_zero() {
+    let rules = r#"
+[rules.fail]
+name = "fail"
+description = "d"
+fails = true
+range = [0, 0]
+pattern = [["Ident", "bad"]]
+
+[rules.warn]
+name = "warn"
+description = "d"
+range = [0, 0]
+pattern = [["Ident", "meh"]]
+"#;
+    let dir = project(
+        "count",
+        &[("rules.toml", rules), ("a.rs", "fn f() { bad; meh; meh; }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--format", "count", "a.rs"]);
+    assert_eq!(String::from_utf8_lossy(&o.stdout).trim(), "1 2");
+    assert_eq!(o.status.code(), Some(1));
+
+    let o = splint(&dir, &["-r", "rules.toml", "--exit-zero", "a.rs"]);
+    assert_eq!(o.status.code(), Some(0));
+}
+
+#[test]
+fn config_search_finds_ancestor_rules() {
+    let dir = project(
+        "config-search",
+        &[
+            ("splint.toml", A),
+            ("a/b/c.rs", "fn f() { bar; }"),
+            ("x/.git/HEAD", ""),
+            ("x/y/z.rs", "fn f() { bar; }"),
+        ],
+    );
+
+    let o = splint(&dir.join("a/b"), &["--config-search", "c.rs"]);
+    assert!(stderr(&o).contains("only in a"), "{}", stderr(&o));
+
+    let o = splint(&dir.join("a/b"), &["c.rs"]);
+    assert!(!stderr(&o).contains("only in a"), "{}", stderr(&o));
+
+    // The search stops at the repository root
+    let o = splint(&dir.join("x/y"), &["--config-search", "z.rs"]);
+    assert!(!stderr(&o).contains("only in a"), "{}", stderr(&o));
+}
+
+const UNWRAP: &str = r#"
+[rules.u]
+name = "u"
+description = "No unwrap"
+range = [0, 3]
+pattern = [["Punct", "."], ["Ident", "unwrap"], ["Delim", "("], ["Delim", ")"]]
+replace = ".expect(\"...\")"
+fixable = true
+"#;
+
+#[test]
+fn fix_writes_replacements() {
+    let dir = project(
+        "fix",
+        &[("rules.toml", UNWRAP), ("a.rs", "fn f() { a.unwrap(); }\n")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--fix", "a.rs"]);
+    assert!(o.status.success(), "{}", stderr(&o));
+    assert_eq!(
+        fs::read_to_string(dir.join("a.rs")).unwrap(),
+        "fn f() { a.expect(\"...\"); }\n"
+    );
+}
+
+#[test]
+fn fix_dry_run_prints_diffs_in_file_order() {
+    let files = ('a'..='f')
+        .map(|c| (format!("{c}.rs"), format!("fn {c}() {{ x.unwrap(); }}\n")))
+        .collect::<Vec<_>>();
+    let mut contents = vec![("rules.toml", UNWRAP)];
+    contents.extend(files.iter().map(|(f, c)| (f.as_str(), c.as_str())));
+    let dir = project("dry-run", &contents);
+
+    let mut args = vec!["-r", "rules.toml", "--fix", "--dry-run"];
+    args.extend(files.iter().map(|(f, _)| f.as_str()));
+    let o = splint(&dir, &args);
+    let out = String::from_utf8_lossy(&o.stdout);
+
+    let expected = files
+        .iter()
+        .map(|(f, c)| {
+            let fixed = c.replace(".unwrap()", ".expect(\"...\")");
+            format!(
+                "--- {f}\n+++ {f}\n@@ -1,1 +1,1 @@\n-{}\n+{}\n",
+                c.trim(),
+                fixed.trim()
+            )
+        })
+        .collect::<String>();
+    assert_eq!(out, expected);
+
+    for (f, c) in &files {
+        assert_eq!(&fs::read_to_string(dir.join(f)).unwrap(), c);
+    }
+}
+
+#[test]
+fn fixing_stdin_only_prints_the_fixed_source() {
+    let dir = project("fix-stdin", &[("rules.toml", UNWRAP)]);
+
+    let o = splint_stdin(
+        &dir,
+        &["-r", "rules.toml", "--fix", "-"],
+        "fn f() { a.unwrap(); }\n",
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&o.stdout),
+        "fn f() { a.expect(\"...\"); }\n"
+    );
+    assert!(
+        stderr(&o).contains("Finished linting 1 files"),
+        "{}",
+        stderr(&o)
+    );
+}
+
+#[test]
+fn no_color_output_has_no_escapes() {
+    let dir = project(
+        "no-color",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() {\n    foo;\n    bar;\n}"),
+        ],
+    );
+
+    let flagged = splint(&dir, &["-r", "rules.toml", "--no-color", "a.rs"]);
+    let env = Command::new(env!("CARGO_BIN_EXE_splint"))
+        .current_dir(&dir)
+        .args(["-r", "rules.toml", "a.rs"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+
+    for o in [flagged, env] {
+        let out = String::from_utf8_lossy(&o.stdout);
+        assert!(out.contains("0 fails, 2 warnings"), "{out}");
+        assert!(!out.contains('\x1b'), "{out:?}");
+        assert!(!stderr(&o).contains('\x1b'), "{:?}", stderr(&o));
+    }
+}
+
+#[test]
+fn exclude_glob_skips_files() {
+    let dir = project(
+        "exclude",
+        &[
+            ("rules.toml", A),
+            ("src/a.rs", "fn f() { bar; }"),
+            ("src/gen/b_generated.rs", "fn f() { bar; }"),
+            ("src/c_generated.rs", "fn f() { bar; }"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--exclude-glob",
+            "**/*_generated.rs",
+            "src",
+        ],
+    );
+    let err = stderr(&o);
+    assert_eq!(err.matches("only in a").count(), 1, "{err}");
+    assert!(err.contains("a.rs"), "{err}");
+    assert!(!err.contains("_generated.rs"), "{err}");
+
+    let o = splint(&dir, &["-r", "rules.toml", "--exclude-glob", "[", "src"]);
+    assert!(
+        stderr(&o).contains("Invalid --exclude-glob '['"),
+        "{}",
+        stderr(&o)
+    );
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn out_of_bounds_ranges_are_config_errors() {
+    let rules = r#"
+[rules.wide]
+name = "wide"
+description = "d"
+range = [0, 5]
+pattern = [["Ident", "a"], ["Ident", "b"]]
+"#;
+    let dir = project(
+        "range",
+        &[("rules.toml", rules), ("a.rs", "fn f() { a b }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "a.rs"]);
+    let err = stderr(&o);
+    assert!(err.contains("Rule 'wide': range [0, 5]"), "{err}");
+    assert!(!err.contains("panicked"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn exit_codes_tell_errors_from_fails() {
+    let rules = r#"
+[rules.fail]
+name = "fail"
+description = "d"
+fails = true
+range = [0, 0]
+pattern = [["Ident", "bad"]]
+"#;
+    let dir = project(
+        "exit-codes",
+        &[
+            ("rules.toml", rules),
+            ("broken.toml", "[rules.x"),
+            ("clean.rs", "fn f() {}"),
+            ("bad.rs", "fn f() { bad; }"),
+        ],
+    );
+    let code = |args: &[&str]| splint(&dir, args).status.code();
+
+    assert_eq!(code(&["-r", "rules.toml", "clean.rs"]), Some(0));
+    assert_eq!(code(&["-r", "rules.toml", "bad.rs"]), Some(1));
+    assert_eq!(code(&["-r", "broken.toml", "clean.rs"]), Some(2));
+    assert_eq!(code(&["-r", "missing.toml", "clean.rs"]), Some(2));
+    assert_eq!(code(&["-r", "rules.toml", "missing.rs"]), Some(2));
+}
+
+#[test]
+fn notes_show_in_human_and_json_output() {
+    let rules = r#"
+[rules.n]
+name = "n"
+description = "d"
+note = "Background on {match}"
+range = [0, 0]
+pattern = [["Ident", "foo"]]
+"#;
+    let dir = project(
+        "note",
+        &[("rules.toml", rules), ("a.rs", "fn f() { foo; }")],
+    );
+
+    let err = stderr(&splint(&dir, &["-r", "rules.toml", "a.rs"]));
+    assert!(err.contains("Background on foo"), "{err}");
+
+    let o = splint(&dir, &["-r", "rules.toml", "-a", "a.rs"]);
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert!(
+        out.contains(r#""level":"note","message":"Background on foo""#),
+        "{out}"
+    );
+}
+
+#[test]
+fn single_thread_matches_default() {
+    let files = (0..12)
+        .map(|i| (format!("f{i}.rs"), format!("fn f{i}() {{ foo; bar; }}")))
+        .collect::<Vec<_>>();
+    let mut contents = vec![("rules.toml", A)];
+    contents.extend(files.iter().map(|(f, c)| (f.as_str(), c.as_str())));
+    let dir = project("threads", &contents);
+
+    let run = |threads: &[&str]| {
+        let mut args = vec!["-r", "rules.toml", "--format", "jsonl"];
+        args.extend(threads);
+        args.extend(files.iter().map(|(f, _)| f.as_str()));
+        // Rules within a file aren't reported in a set order
+        let out = String::from_utf8_lossy(&splint(&dir, &args).stdout).to_string();
+        let mut lines = out.lines().map(str::to_string).collect::<Vec<_>>();
+        lines.sort();
+        lines
+    };
+
+    let serial = run(&["--threads", "1"]);
+    assert_eq!(serial.len(), 24);
+    assert_eq!(serial, run(&[]));
+    assert_eq!(serial, run(&["--threads", "4"]));
+}
+
+#[test]
+fn check_config_validates_without_linting() {
+    let bad = r#"
+[rules.empty]
+name = "empty"
+description = "d"
+range = [0, 0]
+pattern = []
+
+[rules.regex]
+name = "regex"
+description = "d"
+range = [0, 0]
+pattern = [["Ident", "/(/"]]
+"#;
+    let dir = project("check_config", &[("good.toml", A), ("bad.toml", bad)]);
+
+    let o = splint(&dir, &["check-config", "-r", "good.toml"]);
+    assert_eq!(String::from_utf8_lossy(&o.stdout).trim(), "2 rules OK");
+    assert_eq!(o.status.code(), Some(0));
+
+    let o = splint(&dir, &["check-config", "-r", "bad.toml"]);
+    let err = stderr(&o);
+    assert!(err.contains("Rule 'empty': pattern is empty"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+
+    fs::write(
+        dir.join("bad.toml"),
+        &bad[bad.find("[rules.regex]").unwrap()..],
+    )
+    .unwrap();
+    let o = splint(&dir, &["check-config", "-r", "bad.toml"]);
+    let err = stderr(&o);
+    assert!(
+        err.contains("Rule 'regex': needle 0 has an invalid"),
+        "{err}"
+    );
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn fix_keeps_byte_order_marks() {
+    let dir = project(
+        "bom",
+        &[
+            ("rules.toml", UNWRAP),
+            ("a.rs", "\u{feff}fn f() { a.unwrap(); }\r\n"),
+        ],
+    );
+
+    splint(&dir, &["-r", "rules.toml", "--fix", "a.rs"]);
+    assert_eq!(
+        fs::read_to_string(dir.join("a.rs")).unwrap(),
+        "\u{feff}fn f() { a.expect(\"...\"); }\r\n"
+    );
+}
+
+#[test]
+fn first_only_reports_one_match_per_rule_per_file() {
+    let dir = project(
+        "first_only",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() { foo; bar; foo; foo; bar; }"),
+            ("b.rs", "fn f() { foo; foo; }"),
+        ],
+    );
+    let lines = |extra: &[&str]| {
+        let mut args = vec!["-r", "rules.toml", "--format", "jsonl"];
+        args.extend(extra);
+        args.extend(["a.rs", "b.rs"]);
+        let o = splint(&dir, &args);
+        String::from_utf8_lossy(&o.stdout).lines().count()
+    };
+
+    assert_eq!(lines(&[]), 7);
+    assert_eq!(lines(&["--first-only"]), 3);
+}
+
+#[test]
+fn baselines_suppress_existing_lints() {
+    let dir = project(
+        "baseline",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() {\n    foo;\n    bar;\n}"),
+        ],
+    );
+    let run = |extra: &[&str]| {
+        let mut args = vec!["-r", "rules.toml", "--format", "jsonl"];
+        args.extend(extra);
+        args.push("a.rs");
+        splint(&dir, &args)
+    };
+
+    let o = run(&["--baseline", "baseline.json", "--write-baseline"]);
+    assert_eq!(o.status.code(), Some(0));
+    assert!(stderr(&o).contains("Recorded 2 lints in baseline.json"));
+    assert!(run(&["--baseline", "baseline.json"]).stdout.is_empty());
+
+    fs::write(
+        dir.join("a.rs"),
+        "fn f() {\n    foo;\n    bar;\n    bar();\n}",
+    )
+    .unwrap();
+    let o = run(&["--baseline", "baseline.json"]);
+    let lines = String::from_utf8_lossy(&o.stdout).lines().count();
+    assert_eq!(lines, 1);
+}
+
+#[test]
+fn summary_tallies_rules_by_count() {
+    let dir = project(
+        "tally",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() { bar; foo; bar; bar; }"),
+            ("b.rs", "fn f() { foo; }"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &["-r", "rules.toml", "-q", "--summary", "a.rs", "b.rs"],
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&o.stdout),
+        "only_a: 3 occurrences\nshared: 2 occurrences\n"
+    );
+}
+
+#[test]
+fn non_rules_files_say_what_is_wrong() {
+    let dir = project(
+        "shape",
+        &[
+            ("array.json", "[1, 2]"),
+            ("package.json", "{ \"name\": \"web\" }"),
+            ("a.rs", "fn f() {}"),
+        ],
+    );
+
+    let o = splint(&dir, &["-r", "array.json", "a.rs"]);
+    let err = stderr(&o);
+    assert!(err.contains("array.json isn't a rules file"), "{err}");
+    assert!(err.contains("found an array"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+
+    let o = splint(&dir, &["-r", "package.json", "a.rs"]);
+    let err = stderr(&o);
+    assert!(err.contains("missing a `rules` key"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn profile_lists_every_rule() {
+    let dir = project(
+        "profile",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; bar; }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "-q", "--profile", "a.rs"]);
+    let mut lines = stderr(&o)
+        .lines()
+        .map(|l| {
+            let (name, rest) = l.split_once(": ").unwrap();
+            let (ms, matches) = rest.split_once("ms, ").unwrap();
+            assert!(ms.parse::<f64>().unwrap() >= 0.0, "{l}");
+            (name.to_string(), matches.to_string())
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+
+    assert_eq!(
+        lines,
+        [
+            ("only_a".to_string(), "2 matches".to_string()),
+            ("shared".to_string(), "0 matches".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn fix_keeps_mixed_line_endings() {
+    let dir = project(
+        "crlf",
+        &[
+            ("rules.toml", UNWRAP),
+            (
+                "a.rs",
+                "fn f() {\r\n    a.unwrap();\n    b.unwrap();\r\n}\r\n",
+            ),
+        ],
+    );
+
+    splint(&dir, &["-r", "rules.toml", "--fix", "a.rs"]);
+    assert_eq!(
+        fs::read_to_string(dir.join("a.rs")).unwrap(),
+        "fn f() {\r\n    a.expect(\"...\");\n    b.expect(\"...\");\r\n}\r\n"
+    );
+}
+
+#[test]
+fn error_on_fails_past_a_budget() {
+    let dir = project(
+        "budget",
+        &[
+            ("rules.toml", A),
+            ("few.rs", "fn f() { foo; bar; }"),
+            ("many.rs", "fn f() { foo; bar; bar; foo; }"),
+        ],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--error-on", "3", "few.rs"]);
+    assert_eq!(o.status.code(), Some(0));
+
+    let o = splint(&dir, &["-r", "rules.toml", "--error-on", "3", "many.rs"]);
+    assert_eq!(o.status.code(), Some(1));
+}
+
+#[test]
+fn fix_skips_rules_that_arent_fixable() {
+    let hint = UNWRAP.replace("fixable = true", "fixable = false");
+    let source = "fn f() { a.unwrap(); }";
+    let dir = project("hint", &[("rules.toml", &hint), ("a.rs", source)]);
+
+    splint(&dir, &["-r", "rules.toml", "--fix", "a.rs"]);
+    assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), source);
+
+    let o = splint(&dir, &["-r", "rules.toml", "-a", "a.rs"]);
+    let message: serde_json::Value =
+        serde_json::from_str(String::from_utf8_lossy(&o.stdout).lines().next().unwrap()).unwrap();
+    assert_eq!(
+        message["message"]["spans"][0]["suggested_replacement"],
+        ".expect(\"...\")"
+    );
+}
+
+#[test]
+fn coverage_lists_rules_that_never_matched() {
+    let dir = project(
+        "coverage",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; }")],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "-q", "--coverage", "a.rs"]);
+    assert_eq!(stderr(&o), "Rules that never matched:\n  shared\n");
+
+    fs::write(dir.join("a.rs"), "fn f() { foo; bar; }").unwrap();
+    let o = splint(&dir, &["-r", "rules.toml", "-q", "--coverage", "a.rs"]);
+    assert_eq!(stderr(&o), "Every rule matched\n");
+}
+
+#[test]
+fn schema_describes_rules() {
+    let dir = project("schema", &[]);
+    let o = splint(&dir, &["schema"]);
+    assert_eq!(o.status.code(), Some(0));
+
+    let schema: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    let rule = &schema["definitions"]["Rule"]["properties"];
+    for field in ["pattern", "range", "fails"] {
+        assert!(rule.get(field).is_some(), "missing {field}: {rule}");
+    }
+}
+
+#[test]
+fn unreadable_files_dont_stop_the_rest() {
+    let dir = project(
+        "unreadable",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; }")],
+    );
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--format",
+            "jsonl",
+            "a.rs",
+            "missing.rs",
+        ],
+    );
+    let lints = String::from_utf8_lossy(&o.stdout);
+    assert_eq!(lints.lines().count(), 1, "{lints}");
+    assert!(lints.contains("only_a"), "{lints}");
+
+    let err = stderr(&o);
+    assert!(err.contains("missing.rs"), "{err}");
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn relative_paths_shorten_reported_names() {
+    let dir = project(
+        "relative",
+        &[("rules.toml", A), ("a.rs", "fn f() { bar; }")],
+    );
+    let absolute = dir.canonicalize().unwrap().join("a.rs");
+    let absolute = absolute.to_str().unwrap();
+
+    let o = splint(&dir, &["-r", "rules.toml", "--format", "github", absolute]);
+    assert!(String::from_utf8_lossy(&o.stdout).contains(&format!("file={absolute},")));
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--format",
+            "github",
+            "--relative-paths",
+            absolute,
+        ],
+    );
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert!(out.starts_with("::warning file=a.rs,"), "{out}");
+
+    let o = splint(
+        &dir,
+        &["-r", "rules.toml", "-a", "--relative-paths", absolute],
+    );
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert!(
+        out.contains(&format!(r#""src_path":"{absolute}""#)),
+        "{out}"
+    );
+}
+
+#[test]
+fn patterns_dont_match_across_files() {
+    let dir = project(
+        "across",
+        &[
+            ("rules.toml", UNWRAP),
+            ("a.rs", "fn a() {} x."),
+            ("b.rs", "unwrap();"),
+            ("c.rs", "fn c() { x.unwrap(); }"),
+        ],
+    );
+    let count = |files: &[&str]| {
+        let o = splint(
+            &dir,
+            &[&["-r", "rules.toml", "--format", "count"], files].concat(),
+        );
+        assert_eq!(o.status.code(), Some(0), "{}", stderr(&o));
+        String::from_utf8_lossy(&o.stdout).trim().to_string()
+    };
+
+    assert_eq!(count(&["a.rs", "b.rs"]), "0 0");
+    assert_eq!(count(&["a.rs", "b.rs", "c.rs"]), "0 1");
+}
+
+#[test]
+fn summary_json_totals_a_run() {
+    let dir = project(
+        "summary_json",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() { foo; bar; bar; }"),
+            ("b.rs", "fn g() {}"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--format",
+            "summary-json",
+            "a.rs",
+            "b.rs",
+        ],
+    );
+    let summary: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(summary["files"], 2);
+    assert_eq!(summary["fails"], 0);
+    assert_eq!(summary["warnings"], 3);
+    assert_eq!(summary["rules"]["only_a"], 2);
+    assert_eq!(summary["rules"]["shared"], 1);
+}
+
+#[test]
+fn overrides_fail_rules_by_path() {
+    let rules = r#"
+[rules.u]
+name = "u"
+description = "No unwrap"
+fails = true
+range = [0, 1]
+pattern = [["Punct", "."], ["Ident", "unwrap"]]
+
+[[overrides]]
+paths = ["examples/**"]
+fails = { u = false }
+"#;
+    let dir = project(
+        "override_fails",
+        &[
+            ("rules.toml", rules),
+            ("src/a.rs", "fn a() { x.unwrap(); }"),
+            ("examples/a.rs", "fn a() { x.unwrap(); }"),
+        ],
+    );
+
+    let o = splint(&dir, &["-r", "rules.toml", "--format", "count", "src/a.rs"]);
+    assert_eq!(String::from_utf8_lossy(&o.stdout).trim(), "1 0");
+    assert_eq!(o.status.code(), Some(1));
+
+    let o = splint(
+        &dir,
+        &["-r", "rules.toml", "--format", "count", "examples/a.rs"],
+    );
+    assert_eq!(String::from_utf8_lossy(&o.stdout).trim(), "0 1");
+    assert_eq!(o.status.code(), Some(0));
+
+    fs::write(
+        dir.join("rules.toml"),
+        rules.replace("{ u = false }", "{ v = false }"),
+    )
+    .unwrap();
+    let o = splint(&dir, &["-r", "rules.toml", "src/a.rs"]);
+    assert!(stderr(&o).contains("no rule named 'v'"), "{}", stderr(&o));
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn progress_names_each_file() {
+    let dir = project(
+        "progress",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn a() {}"),
+            ("b.rs", "fn b() {}"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--progress",
+            "--threads",
+            "1",
+            "a.rs",
+            "b.rs",
+        ],
+    );
+    let err = stderr(&o);
+    assert!(err.contains("Linting a.rs (1/2)"), "{err}");
+    assert!(err.contains("Linting b.rs (2/2)"), "{err}");
+
+    let o = splint(&dir, &["-r", "rules.toml", "a.rs", "b.rs"]);
+    assert!(!stderr(&o).contains("Linting a.rs"), "{}", stderr(&o));
+}
+
+#[test]
+fn explain_describes_a_rule() {
+    let rules = r#"
+[rules.u]
+name = "Disallow Unwrap"
+description = "No unwrap"
+link = "https://example.com/unwrap"
+fails = true
+range = [0, 1]
+pattern = [["Punct", "."], ["Ident", "unwrap"]]
+"#;
+    let dir = project("explain", &[("rules.toml", rules)]);
+
+    let o = splint(&dir, &["explain", "Disallow Unwrap", "-r", "rules.toml"]);
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert!(
+        out.starts_with("Disallow Unwrap (error)\nNo unwrap\n"),
+        "{out}"
+    );
+    assert!(out.contains("link: https://example.com/unwrap"), "{out}");
+    assert_eq!(o.status.code(), Some(0));
+
+    let o = splint(&dir, &["explain", "u", "-r", "rules.toml"]);
+    assert!(String::from_utf8_lossy(&o.stdout).contains("No unwrap"));
+
+    let o = splint(&dir, &["explain", "missing", "-r", "rules.toml"]);
+    assert!(
+        stderr(&o).contains("No rule named 'missing'"),
+        "{}",
+        stderr(&o)
+    );
+    assert_eq!(o.status.code(), Some(2));
+}
+
+#[test]
+fn markdown_input_reports_markdown_lines() {
+    let dir = project(
+        "markdown",
+        &[
+            ("rules.toml", UNWRAP),
+            (
+                "docs/a.md",
+                "Some text\n\n```rust\nlet a = x.unwrap();\n```\n",
+            ),
+            ("docs/b.rs", "fn b() { x.unwrap(); }"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &[
+            "-r",
+            "rules.toml",
+            "--input-format",
+            "markdown",
+            "--format",
+            "github",
+            "docs",
+        ],
+    );
+    let out = String::from_utf8_lossy(&o.stdout);
+    assert_eq!(out.lines().count(), 1, "{out}");
+    assert!(
+        out.starts_with("::warning file=docs/a.md,line=4,col=10,"),
+        "{out}"
+    );
+}
+
+#[test]
+fn globs_expand_braces_and_recursion() {
+    let dir = project(
+        "globs",
+        &[
+            ("rules.toml", A),
+            ("src/a.rs", "fn a() { bar; }"),
+            ("src/b.rs", "fn b() { bar; }"),
+            ("src/c.rs", "fn c() { bar; }"),
+            ("src/deep/d.rs", "fn d() { bar; }"),
+            ("src/deep/d_generated.rs", "fn d() { bar; }"),
+        ],
+    );
+    let files = |args: &[&str]| {
+        let o = splint(
+            &dir,
+            &[&["-r", "rules.toml", "--format", "github"], args].concat(),
+        );
+        let mut files = String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(|l| l.split(['=', ',']).nth(1).unwrap().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    };
+
+    assert_eq!(files(&["src/{a,b}.rs"]), ["src/a.rs", "src/b.rs"]);
+    assert_eq!(files(&["src/*.rs"]), ["src/a.rs", "src/b.rs", "src/c.rs"]);
+    assert_eq!(
+        files(&["**/*.rs"]),
+        [
+            "src/a.rs",
+            "src/b.rs",
+            "src/c.rs",
+            "src/deep/d.rs",
+            "src/deep/d_generated.rs"
+        ]
+    );
+    assert_eq!(
+        files(&["--exclude-glob", "**/*_generated.rs", "src/deep"]),
+        ["src/deep/d.rs"]
+    );
+}
+
+#[test]
+fn junit_reports_a_suite_per_file() {
+    let dir = project(
+        "junit",
+        &[
+            ("rules.toml", A),
+            ("a.rs", "fn f() { foo; bar; }"),
+            ("b.rs", "fn g() { bar; }"),
+        ],
+    );
+
+    let o = splint(
+        &dir,
+        &["-r", "rules.toml", "--format", "junit", "a.rs", "b.rs"],
+    );
+    let xml = String::from_utf8_lossy(&o.stdout);
+    assert!(xml.starts_with("<?xml"), "{xml}");
+    assert!(
+        xml.contains("<testsuite name=\"a.rs\" tests=\"2\" failures=\"0\">"),
+        "{xml}"
+    );
+    assert!(
+        xml.contains("<testsuite name=\"b.rs\" tests=\"1\" failures=\"0\">"),
+        "{xml}"
+    );
+    assert_eq!(o.status.code(), Some(0));
+}
+
+#[test]
+fn rules_can_be_read_from_file_urls() {
+    let dir = project(
+        "file_url",
+        &[("rules.toml", UNWRAP), ("a.rs", "fn a() { x.unwrap(); }")],
+    );
+    let url = format!("file://{}", dir.join("rules.toml").display());
+
+    let o = splint(&dir, &["-r", &url, "--format", "count", "a.rs"]);
+    assert_eq!(
+        String::from_utf8_lossy(&o.stdout).trim(),
+        "0 1",
+        "{}",
+        stderr(&o)
+    );
+    assert_eq!(o.status.code(), Some(0));
+}